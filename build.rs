@@ -0,0 +1,51 @@
+//! Embed every sound file in the binary, so the sound bank manifest can refer to them by path.
+//!
+//! The sound bank and music manifests are checked here too, so mistakes fail the build instead of
+//! the game.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// Only the parsing and checks are used here
+#[allow(dead_code)]
+#[path = "src/audio/bank.rs"]
+mod bank;
+
+fn main() {
+    let root = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let root = Path::new(&root);
+
+    let mut files = Vec::new();
+    for dir in ["assets", "cc0"] {
+        println!("cargo:rerun-if-changed={}", dir);
+
+        for entry in fs::read_dir(root.join(dir)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "ogg") {
+                let name = path.file_name().unwrap().to_str().unwrap();
+                files.push(format!("{}/{}", dir, name));
+            }
+        }
+    }
+    files.sort();
+
+    let names = files.iter().map(String::as_str).collect::<Vec<_>>();
+    let sounds = fs::read_to_string(root.join("assets/sounds.json")).unwrap();
+    let bank = bank::parse_bank(&sounds, &names)
+        .unwrap_or_else(|err| panic!("assets/sounds.json: {}", err));
+    let music = fs::read_to_string(root.join("assets/music.json")).unwrap();
+    if let Err(err) = bank::parse_music(&music, &bank) {
+        panic!("assets/music.json: {}", err);
+    }
+
+    let mut table = String::from("&[\n");
+    for file in files {
+        let path = root.join(&file).display().to_string();
+        table += &format!("    ({:?}, include_bytes!({:?})),\n", file, path);
+    }
+    table += "]\n";
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("sound_files.rs"), table).unwrap();
+}
@@ -1,395 +1,398 @@
+use crate::image::{Atlas, Flip};
 use randomize::PCG32;
-use std::time::{Duration, Instant};
-use tiled::PropertyValue;
-
-pub(crate) trait Animated {
-    fn animate(&mut self) -> usize;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// A single frame of a clip, referring to a frame of the sprite atlas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Frame {
+    pub(crate) index: usize,
+    pub(crate) duration: Duration,
 }
 
-struct Frame {
-    index: usize,
-    duration: Duration,
-}
+/// A named sequence of frames, e.g. an Aseprite frame tag.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Clip(pub(crate) Vec<Frame>);
 
-struct Animation {
-    frames: Vec<Frame>,
-    current_index: usize,
-    start_time: Instant,
-}
+/// How a state plays its clip.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Mode {
+    /// Restart from the first frame after the last frame.
+    Loop,
 
-impl Animation {
-    fn new(frames: Vec<Frame>) -> Self {
-        Self {
-            frames,
-            current_index: 0,
-            start_time: Instant::now(),
-        }
-    }
-
-    fn get_frame(&self) -> &Frame {
-        &self.frames[self.current_index % self.frames.len()]
-    }
-
-    fn update(&mut self) -> usize {
-        let dur = self.get_frame().duration;
-
-        if self.start_time.elapsed() > dur {
-            self.current_index += 1;
-            self.current_index %= self.frames.len();
-            self.start_time = Instant::now();
-        }
-
-        self.frames[self.current_index].index
-    }
-
-    fn reset(&mut self) {
-        self.current_index = 0;
-        self.start_time = Instant::now();
-    }
-}
-
-impl Frame {
-    fn new(index: usize, duration: Duration) -> Self {
-        Self { index, duration }
-    }
-}
-
-pub(crate) struct FrogAnims {
-    playing: FrogCurrentAnim,
-    idle_right: Animation,
-    idle_left: Animation,
-    hop_right: Animation,
-    hop_left: Animation,
-}
+    /// Stop on the last frame, then move to the `next` state if there is one.
+    Once,
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub(crate) enum FrogCurrentAnim {
-    IdleRight,
-    IdleLeft,
-    HopRight,
-    HopLeft,
+    /// Play forward and backward, without repeating the end frames.
+    PingPong,
 }
 
-pub(crate) struct JeanAnims {
-    playing: JeanCurrentAnim,
-    idle_right: Animation,
-    idle_left: Animation,
-    walk_right: Animation,
-    walk_left: Animation,
+/// Animation state machine definition, loaded from JSON.
+///
+/// ```json
+/// {
+///   "initial": "idle",
+///   "states": {
+///     "idle": { "clip": "idle", "on": { "hop": "hop" } },
+///     "hop": {
+///       "clip": "hop",
+///       "mode": "once",
+///       "next": "idle",
+///       "events": { "0": ["play_sound:jump"], "1": ["move"], "4": ["land"] }
+///     }
+///   }
+/// }
+/// ```
+///
+/// Each state plays a clip from the sprite atlas. The `on` table maps events to the states they
+/// transition to; events that a state does not list are ignored. The `events` table lists frame
+/// events by position in the clip, which are emitted when the frame is entered.
+#[derive(Deserialize)]
+struct Definition {
+    initial: String,
+    states: HashMap<String, StateDefinition>,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub(crate) enum JeanCurrentAnim {
-    IdleRight,
-    IdleLeft,
-    WalkRight,
-    WalkLeft,
+#[derive(Deserialize)]
+struct StateDefinition {
+    clip: String,
+    #[serde(default = "default_mode")]
+    mode: Mode,
+    next: Option<String>,
+    #[serde(default)]
+    on: HashMap<String, String>,
+    #[serde(default)]
+    events: HashMap<usize, Vec<String>>,
 }
 
-pub(crate) struct BlobAnims {
-    playing: BlobCurrentAnim,
-    idle_right: Animation,
-    idle_left: Animation,
-    bounce_right: Animation,
-    bounce_left: Animation,
+struct State {
+    name: String,
+    frames: Vec<Frame>,
+    mode: Mode,
+    next: Option<usize>,
+    on: HashMap<String, usize>,
+    events: Vec<Vec<String>>,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub(crate) enum BlobCurrentAnim {
-    IdleRight,
-    IdleLeft,
-    BounceRight,
-    BounceLeft,
+/// Generic animation controller with named states and declarative transitions.
+pub(crate) struct Animator {
+    states: Vec<State>,
+    current: usize,
+    position: usize,
+    reverse: bool,
+    /// Game time spent on the current frame.
+    elapsed: Duration,
+    flip: Flip,
+
+    /// Frame events since the last call to `animate`.
+    pending: Vec<String>,
+
+    /// Frame events emitted by the last call to `animate`.
+    events: Vec<String>,
 }
 
-pub(crate) struct FireAnims {
-    burn: Animation,
+fn default_mode() -> Mode {
+    Mode::Loop
 }
 
-impl FrogAnims {
-    pub(crate) fn new() -> Self {
-        Self {
-            playing: FrogCurrentAnim::IdleRight,
-            idle_right: Animation::new(vec![Frame::new(0, Duration::from_secs(1))]),
-            idle_left: Animation::new(vec![Frame::new(5, Duration::from_secs(1))]),
-            hop_right: Animation::new(vec![
-                Frame::new(0, Duration::from_millis(100)),
-                Frame::new(1, Duration::from_millis(100)),
-                Frame::new(2, Duration::from_millis(100)),
-                Frame::new(3, Duration::from_millis(100)),
-                Frame::new(4, Duration::from_millis(200)),
-            ]),
-            hop_left: Animation::new(vec![
-                Frame::new(5, Duration::from_millis(100)),
-                Frame::new(6, Duration::from_millis(100)),
-                Frame::new(7, Duration::from_millis(100)),
-                Frame::new(8, Duration::from_millis(100)),
-                Frame::new(9, Duration::from_millis(200)),
-            ]),
-        }
-    }
+impl Animator {
+    pub(crate) fn new(atlas: &Atlas, json: &str) -> Self {
+        let definition: Definition = serde_json::from_str(json).expect("Invalid animation");
+
+        // States are stored in a stable order so transitions can refer to them by index
+        let mut names = definition.states.keys().cloned().collect::<Vec<_>>();
+        names.sort_unstable();
+        let index = |name: &str| -> usize {
+            names
+                .iter()
+                .position(|other| other == name)
+                .unwrap_or_else(|| panic!("Animation state {} does not exist", name))
+        };
 
-    pub(crate) fn set(&mut self, next: FrogCurrentAnim) {
-        self.playing = next;
+        let states = names
+            .iter()
+            .map(|name| {
+                let state = &definition.states[name];
+                let frames = atlas.clip(&state.clip).0.clone();
+
+                let mut events = vec![Vec::new(); frames.len()];
+                for (&position, names) in &state.events {
+                    assert!(
+                        position < frames.len(),
+                        "Animation state {} has events for missing frame {}",
+                        name,
+                        position
+                    );
+                    events[position].extend(names.iter().cloned());
+                }
 
-        // Reset the animation
-        let animation = match self.playing {
-            FrogCurrentAnim::IdleRight => &mut self.idle_right,
-            FrogCurrentAnim::IdleLeft => &mut self.idle_left,
-            FrogCurrentAnim::HopRight => &mut self.hop_right,
-            FrogCurrentAnim::HopLeft => &mut self.hop_left,
+                State {
+                    name: name.clone(),
+                    frames,
+                    mode: state.mode,
+                    next: state.next.as_deref().map(index),
+                    on: state
+                        .on
+                        .iter()
+                        .map(|(event, target)| (event.clone(), index(target)))
+                        .collect(),
+                    events,
+                }
+            })
+            .collect();
+
+        let mut animator = Self {
+            states,
+            current: 0,
+            position: 0,
+            reverse: false,
+            elapsed: Duration::ZERO,
+            flip: Flip::empty(),
+            pending: Vec::new(),
+            events: Vec::new(),
         };
+        animator.enter(index(&definition.initial));
 
-        animation.reset();
+        animator
     }
 
-    pub(crate) fn playing(&self) -> FrogCurrentAnim {
-        self.playing
+    /// Name of the current state.
+    pub(crate) fn state(&self) -> &str {
+        &self.states[self.current].name
     }
 
-    pub(crate) fn get_frame_index(&self) -> usize {
-        match self.playing {
-            FrogCurrentAnim::IdleRight => self.idle_right.get_frame().index,
-            FrogCurrentAnim::IdleLeft => self.idle_left.get_frame().index,
-            FrogCurrentAnim::HopRight => self.hop_right.get_frame().index,
-            FrogCurrentAnim::HopLeft => self.hop_left.get_frame().index,
+    /// Send an event to the state machine. Returns `true` when it caused a transition.
+    pub(crate) fn trigger(&mut self, event: &str) -> bool {
+        match self.states[self.current].on.get(event) {
+            Some(&next) => {
+                self.enter(next);
+                true
+            }
+            None => false,
         }
     }
-}
 
-impl Animated for FrogAnims {
-    fn animate(&mut self) -> usize {
-        // Hopping animations will switch to idle after the animation cycle completes
-        match self.playing {
-            FrogCurrentAnim::IdleRight => self.idle_right.update(),
-            FrogCurrentAnim::IdleLeft => self.idle_left.update(),
-            FrogCurrentAnim::HopRight => {
-                let last_frame_index = self.hop_right.get_frame().index;
-                let frame_index = self.hop_right.update();
-
-                if last_frame_index == 4 && frame_index == 0 {
-                    self.set(FrogCurrentAnim::IdleRight);
-                }
+    /// Atlas index of the current frame.
+    pub(crate) fn frame_index(&self) -> usize {
+        self.states[self.current].frames[self.position].index
+    }
 
-                frame_index
-            }
-            FrogCurrentAnim::HopLeft => {
-                let last_frame_index = self.hop_left.get_frame().index;
-                let frame_index = self.hop_left.update();
+    pub(crate) fn flip(&self) -> Flip {
+        self.flip
+    }
 
-                if last_frame_index == 9 && frame_index == 5 {
-                    self.set(FrogCurrentAnim::IdleLeft);
-                }
+    /// Sprite sheets only store right-facing frames; left-facing animations are mirrored.
+    pub(crate) fn face_left(&mut self, left: bool) {
+        self.flip.set(Flip::HORIZONTAL, left);
+    }
 
-                frame_index
-            }
-        }
+    /// Frame events emitted by the last call to `animate`.
+    pub(crate) fn events(&self) -> impl Iterator<Item = &str> {
+        self.events.iter().map(String::as_str)
     }
-}
 
-impl JeanAnims {
-    pub(crate) fn new() -> Self {
-        Self {
-            playing: JeanCurrentAnim::IdleRight,
-            idle_right: Animation::new(vec![Frame::new(0, Duration::from_secs(1))]),
-            idle_left: Animation::new(vec![Frame::new(9, Duration::from_secs(1))]),
-            walk_right: Animation::new(vec![
-                Frame::new(1, Duration::from_millis(80)),
-                Frame::new(2, Duration::from_millis(80)),
-                Frame::new(3, Duration::from_millis(80)),
-                Frame::new(4, Duration::from_millis(80)),
-                Frame::new(5, Duration::from_millis(80)),
-                Frame::new(6, Duration::from_millis(80)),
-                Frame::new(7, Duration::from_millis(80)),
-                Frame::new(8, Duration::from_millis(80)),
-            ]),
-            walk_left: Animation::new(vec![
-                Frame::new(10, Duration::from_millis(80)),
-                Frame::new(11, Duration::from_millis(80)),
-                Frame::new(12, Duration::from_millis(80)),
-                Frame::new(13, Duration::from_millis(80)),
-                Frame::new(14, Duration::from_millis(80)),
-                Frame::new(15, Duration::from_millis(80)),
-                Frame::new(16, Duration::from_millis(80)),
-                Frame::new(17, Duration::from_millis(80)),
-            ]),
-        }
+    /// Start the current clip on a random frame, to desynchronize identical entities.
+    pub(crate) fn randomize(&mut self, random: &mut PCG32) {
+        self.position = random.next_u32() as usize % self.states[self.current].frames.len();
     }
 
-    pub(crate) fn set(&mut self, next: JeanCurrentAnim) {
-        self.playing = next;
+    /// Advance the animation by `delta` game time and return the atlas index of the current frame.
+    ///
+    /// Frame events entered since the previous call become available from `events`.
+    pub(crate) fn animate(&mut self, delta: Duration) -> usize {
+        self.elapsed += delta;
+
+        // Carry the time left over from each frame into the next, so clips keep their speed
+        loop {
+            let duration = self.states[self.current].frames[self.position].duration;
+            if duration.is_zero() || self.elapsed < duration {
+                break;
+            }
 
-        // Reset the animation
-        let animation = match self.playing {
-            JeanCurrentAnim::IdleRight => &mut self.idle_right,
-            JeanCurrentAnim::IdleLeft => &mut self.idle_left,
-            JeanCurrentAnim::WalkRight => &mut self.walk_right,
-            JeanCurrentAnim::WalkLeft => &mut self.walk_left,
-        };
+            let remainder = self.elapsed - duration;
+            self.advance();
+            self.elapsed = remainder;
+        }
 
-        animation.reset();
-    }
+        self.events = std::mem::take(&mut self.pending);
 
-    pub(crate) fn playing(&self) -> JeanCurrentAnim {
-        self.playing
+        self.frame_index()
     }
 
-    pub(crate) fn to_idle(&self) -> JeanCurrentAnim {
-        match self.playing() {
-            JeanCurrentAnim::IdleLeft => JeanCurrentAnim::IdleLeft,
-            JeanCurrentAnim::IdleRight => JeanCurrentAnim::IdleRight,
-            JeanCurrentAnim::WalkLeft => JeanCurrentAnim::IdleLeft,
-            JeanCurrentAnim::WalkRight => JeanCurrentAnim::IdleRight,
+    /// Move to the next frame of the current state.
+    fn advance(&mut self) {
+        let state = &self.states[self.current];
+        let last = state.frames.len() - 1;
+
+        match state.mode {
+            Mode::Loop => self.seek((self.position + 1) % state.frames.len()),
+            Mode::Once if self.position < last => self.seek(self.position + 1),
+            Mode::Once => {
+                if let Some(next) = state.next {
+                    self.enter(next);
+                }
+            }
+            Mode::PingPong if last == 0 => (),
+            Mode::PingPong => {
+                if self.position == last {
+                    self.reverse = true;
+                } else if self.position == 0 {
+                    self.reverse = false;
+                }
+
+                if self.reverse {
+                    self.seek(self.position - 1);
+                } else {
+                    self.seek(self.position + 1);
+                }
+            }
         }
     }
 
-    pub(crate) fn to_walking(&self) -> JeanCurrentAnim {
-        match self.playing() {
-            JeanCurrentAnim::IdleLeft => JeanCurrentAnim::WalkLeft,
-            JeanCurrentAnim::IdleRight => JeanCurrentAnim::WalkRight,
-            JeanCurrentAnim::WalkLeft => JeanCurrentAnim::WalkLeft,
-            JeanCurrentAnim::WalkRight => JeanCurrentAnim::WalkRight,
-        }
+    fn enter(&mut self, state: usize) {
+        self.current = state;
+        self.reverse = false;
+        self.elapsed = Duration::ZERO;
+        self.seek(0);
     }
-}
 
-impl Animated for JeanAnims {
-    fn animate(&mut self) -> usize {
-        match self.playing {
-            JeanCurrentAnim::IdleRight => self.idle_right.update(),
-            JeanCurrentAnim::IdleLeft => self.idle_left.update(),
-            JeanCurrentAnim::WalkRight => self.walk_right.update(),
-            JeanCurrentAnim::WalkLeft => self.walk_left.update(),
-        }
+    fn seek(&mut self, position: usize) {
+        self.position = position;
+
+        let events = &self.states[self.current].events[position];
+        self.pending.extend(events.iter().cloned());
     }
 }
 
-impl BlobAnims {
-    pub(crate) fn new(playing: BlobCurrentAnim) -> Self {
-        Self {
-            playing,
-            idle_right: Animation::new(vec![Frame::new(0, Duration::from_secs(1))]),
-            idle_left: Animation::new(vec![Frame::new(8, Duration::from_secs(1))]),
-            bounce_right: Animation::new(vec![
-                Frame::new(1, Duration::from_millis(80)),
-                Frame::new(2, Duration::from_millis(80)),
-                Frame::new(3, Duration::from_millis(80)),
-                Frame::new(4, Duration::from_millis(80)),
-                Frame::new(5, Duration::from_millis(80)),
-                Frame::new(6, Duration::from_millis(80)),
-                Frame::new(7, Duration::from_millis(120)),
-            ]),
-            bounce_left: Animation::new(vec![
-                Frame::new(9, Duration::from_millis(80)),
-                Frame::new(10, Duration::from_millis(80)),
-                Frame::new(11, Duration::from_millis(80)),
-                Frame::new(12, Duration::from_millis(80)),
-                Frame::new(13, Duration::from_millis(80)),
-                Frame::new(14, Duration::from_millis(80)),
-                Frame::new(15, Duration::from_millis(120)),
-            ]),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANIMATION: &str = r#"{
+        "initial": "idle",
+        "states": {
+            "idle": { "clip": "idle", "on": { "bounce": "bounce" } },
+            "bounce": {
+                "clip": "bounce",
+                "mode": "once",
+                "next": "idle",
+                "events": { "0": ["play_sound:splat"], "6": ["land"] }
+            }
         }
-    }
-
-    pub(crate) fn set(&mut self, next: BlobCurrentAnim) {
-        self.playing = next;
+    }"#;
 
-        // Reset the animation
-        let animation = match self.playing {
-            BlobCurrentAnim::IdleRight => &mut self.idle_right,
-            BlobCurrentAnim::IdleLeft => &mut self.idle_left,
-            BlobCurrentAnim::BounceRight => &mut self.bounce_right,
-            BlobCurrentAnim::BounceLeft => &mut self.bounce_left,
-        };
+    fn animator() -> Animator {
+        let atlas = Atlas::new(
+            include_bytes!("../assets/blob.png"),
+            include_str!("../assets/blob.json"),
+        );
 
-        animation.reset();
+        Animator::new(&atlas, ANIMATION)
     }
 
-    pub(crate) fn playing(&self) -> BlobCurrentAnim {
-        self.playing
+    /// Expire the current frame and advance the animation.
+    fn step(animator: &mut Animator) -> usize {
+        let state = &animator.states[animator.current];
+        let duration = state.frames[animator.position].duration;
+
+        animator.animate(duration - animator.elapsed)
     }
 
-    pub(crate) fn get_frame_index(&self) -> usize {
-        match self.playing {
-            BlobCurrentAnim::IdleRight => self.idle_right.get_frame().index,
-            BlobCurrentAnim::IdleLeft => self.idle_left.get_frame().index,
-            BlobCurrentAnim::BounceRight => self.bounce_right.get_frame().index,
-            BlobCurrentAnim::BounceLeft => self.bounce_left.get_frame().index,
-        }
+    #[test]
+    fn test_animator_transitions() {
+        let mut animator = animator();
+        assert_eq!(animator.state(), "idle");
+
+        // Events without a transition are ignored
+        assert!(!animator.trigger("walk"));
+        assert!(animator.trigger("bounce"));
+        assert!(!animator.trigger("bounce"));
+        assert_eq!(animator.frame_index(), 1);
+
+        // The clip plays once and then returns to idle
+        let frames = (0..7).map(|_| step(&mut animator)).collect::<Vec<_>>();
+        assert_eq!(frames, [2, 3, 4, 5, 6, 7, 0]);
+        assert_eq!(animator.state(), "idle");
     }
-}
 
-impl Animated for BlobAnims {
-    fn animate(&mut self) -> usize {
-        match self.playing {
-            BlobCurrentAnim::IdleRight => self.idle_right.update(),
-            BlobCurrentAnim::IdleLeft => self.idle_left.update(),
-            BlobCurrentAnim::BounceRight => {
-                let last_frame_index = self.bounce_right.get_frame().index;
-                let frame_index = self.bounce_right.update();
-
-                if last_frame_index == 7 && frame_index == 1 {
-                    self.set(BlobCurrentAnim::IdleRight);
-                    self.get_frame_index()
-                } else {
-                    frame_index
-                }
-            }
-            BlobCurrentAnim::BounceLeft => {
-                let last_frame_index = self.bounce_left.get_frame().index;
-                let frame_index = self.bounce_left.update();
+    #[test]
+    fn test_animator_events() {
+        let mut animator = animator();
+        fn events(animator: &Animator) -> Vec<&str> {
+            animator.events().collect()
+        }
 
-                if last_frame_index == 15 && frame_index == 9 {
-                    self.set(BlobCurrentAnim::IdleLeft);
-                    self.get_frame_index()
-                } else {
-                    frame_index
-                }
-            }
+        // Events from transitions are emitted on the next tick
+        animator.trigger("bounce");
+        assert!(events(&animator).is_empty());
+        animator.animate(Duration::ZERO);
+        assert_eq!(events(&animator), ["play_sound:splat"]);
+        animator.animate(Duration::ZERO);
+        assert!(events(&animator).is_empty());
+
+        for _ in 0..5 {
+            step(&mut animator);
+            assert!(events(&animator).is_empty());
         }
+        step(&mut animator);
+        assert_eq!(events(&animator), ["land"]);
     }
-}
 
-impl BlobCurrentAnim {
-    pub(crate) fn new(random: &mut PCG32, direction: Option<&PropertyValue>) -> Self {
-        direction
-            .map(|prop| match prop {
-                PropertyValue::StringValue(direction) => match direction.as_str() {
-                    "left" => Some(BlobCurrentAnim::IdleLeft),
-                    "right" => Some(BlobCurrentAnim::IdleRight),
-                    _ => None,
-                },
-                _ => None,
-            })
-            .flatten()
-            .unwrap_or_else(|| {
-                if random.next_u32() & 1 == 0 {
-                    BlobCurrentAnim::IdleLeft
-                } else {
-                    BlobCurrentAnim::IdleRight
-                }
-            })
+    #[test]
+    fn test_animator_ping_pong() {
+        let mut animator = animator();
+        let bounce = animator
+            .states
+            .iter_mut()
+            .find(|state| state.name == "bounce");
+        bounce.unwrap().mode = Mode::PingPong;
+        animator.trigger("bounce");
+
+        let frames = (0..14).map(|_| step(&mut animator)).collect::<Vec<_>>();
+        assert_eq!(frames, [2, 3, 4, 5, 6, 7, 6, 5, 4, 3, 2, 1, 2, 3]);
     }
-}
 
-impl FireAnims {
-    pub(crate) fn new(random: &mut PCG32) -> Self {
-        let mut burn = Animation::new(vec![
-            Frame::new(0, Duration::from_millis(30)),
-            Frame::new(1, Duration::from_millis(40)),
-            Frame::new(2, Duration::from_millis(30)),
-            Frame::new(3, Duration::from_millis(50)),
-            Frame::new(4, Duration::from_millis(35)),
-            Frame::new(5, Duration::from_millis(40)),
-        ]);
-        burn.current_index = random.next_u32() as usize % burn.frames.len();
-
-        Self { burn }
-    }
-}
+    #[test]
+    fn test_animator_timing() {
+        let mut animator = animator();
+        let bounce = animator
+            .states
+            .iter_mut()
+            .find(|state| state.name == "bounce")
+            .unwrap();
+        bounce.mode = Mode::Loop;
+        let frames = bounce.frames.clone();
+        animator.trigger("bounce");
+
+        // Ten seconds of 60 Hz ticks
+        let tick = Duration::from_nanos(16_666_667);
+        let mut advanced = 0;
+        let mut position = animator.position;
+        for _ in 0..600 {
+            animator.animate(tick);
+            if animator.position != position {
+                position = animator.position;
+                advanced += 1;
+            }
+        }
 
-impl Animated for FireAnims {
-    fn animate(&mut self) -> usize {
-        self.burn.update()
+        // Frames end exactly when their durations add up, without losing time to the tick rate
+        let total = tick * 600;
+        let mut expected = 0;
+        let mut end = Duration::ZERO;
+        loop {
+            end += frames[expected % frames.len()].duration;
+            if end > total {
+                break;
+            }
+            expected += 1;
+        }
+        assert_eq!(advanced, expected);
+        assert_eq!(
+            animator.elapsed,
+            total - (end - frames[expected % frames.len()].duration)
+        );
     }
 }
@@ -1,62 +1,851 @@
+use self::bank::{MusicDefinition, SoundDefinition};
+use crate::settings::Volume;
+use crate::WIDTH;
 use anyhow::Result;
-use kira::instance::InstanceSettings;
+use kira::instance::{
+    handle::InstanceHandle, InstanceSettings, InstanceState, StopInstanceSettings,
+};
 use kira::manager::{AudioManager, AudioManagerSettings};
+use kira::mixer::{SubTrackHandle, SubTrackSettings};
+use kira::parameter::{handle::ParameterHandle, tween::Tween, Mapping, ParameterSettings};
 use kira::sound::{self, handle::SoundHandle, SoundSettings};
+use kira::Value;
+use log::{debug, warn};
+use shipyard::EntityId;
+use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
+use std::time::Duration;
+use ultraviolet::Vec2;
+
+mod bank;
+
+/// Every `.ogg` file in `assets` and `cc0`, by path. Generated by `build.rs`.
+const SOUND_FILES: &[(&str, &[u8])] = include!(concat!(env!("OUT_DIR"), "/sound_files.rs"));
+
+/// Sound bank manifest, mapping sound ids to definitions.
+///
+/// ```json
+/// {
+///   "jump": { "file": "assets/jump.ogg", "volume": 0.8, "loop": false, "max_instances": 4 }
+/// }
+/// ```
+///
+/// Only `file` is required. A sound is not played again while `max_instances` of it are playing, or
+/// within `min_interval` seconds of the last time it was played. When all voices are busy, sounds
+/// with the same or higher `priority` stop the oldest voice with the lowest priority. Sounds with
+/// `duck` set lower the music while they play.
+const SOUND_BANK: &str = include_str!("../assets/sounds.json");
+
+/// Adaptive music definition.
+///
+/// ```json
+/// {
+///   "fade": 2.0,
+///   "duck": 0.5,
+///   "states": {
+///     "calm": { "pads": 0.8 },
+///     "threat": { "pads": 0.8, "drums": 1.0 }
+///   }
+/// }
+/// ```
+///
+/// Each state sets the volume of music layers, which are looping sounds from the sound bank. All
+/// layers start together and stay in sync; layers that a state does not list are silent. Changing
+/// state crossfades the layers over `fade` seconds, and ducking scales them by `duck`.
+const MUSIC: &str = include_str!("../assets/music.json");
+
+// Fade time for ducking the music, in seconds
+const DUCK_FADE: f64 = 0.1;
+
+// Positional sounds play at full volume within this distance of the listener, in pixels
+const FULL_VOLUME_DISTANCE: f32 = 64.0;
+
+// Positional sounds beyond this distance are not played
+const CUTOFF_DISTANCE: f32 = 240.0;
+
+// Stereo separation at the edge of the screen; 0.5 would pan fully to one side
+const MAX_PANNING: f32 = 0.4;
+
+// Emitter volume and panning are only sent to the audio thread when they change by this much
+const EMITTER_THRESHOLD: f64 = 0.01;
+
+// Most sounds that play at once; more important sounds stop the oldest, least important voice
+const MAX_VOICES: usize = 16;
+
+// Fade out time for stolen voices, in seconds
+const STEAL_FADE: f64 = 0.05;
 
 pub(crate) struct Player {
-    _manager: AudioManager,
-    sounds: Sounds,
+    bank: HashMap<String, SoundDefinition>,
+    backend: Backend,
+    voices: Vec<Voice>,
+    music: Music,
+    emitters: HashMap<EntityId, Emitter>,
+
+    /// Game time of the last update.
+    now: Duration,
+    last_played: HashMap<String, Duration>,
+}
+
+enum Backend {
+    Kira(Box<Kira>),
+
+    /// Silent backend for machines without an audio device.
+    Null(Null),
+}
+
+/// Plays nothing. Tests can check which sounds were requested.
+#[derive(Default)]
+struct Null {
+    #[cfg(test)]
+    requested: Vec<String>,
+    #[cfg(test)]
+    ducked_by: Vec<String>,
+}
+
+struct Kira {
+    manager: AudioManager,
+    sounds: HashMap<String, SoundHandle>,
+
+    /// Mixer tracks for music layers and sound effects, under the main track.
+    music: SubTrackHandle,
+    sfx: SubTrackHandle,
+}
+
+struct Music {
+    definition: MusicDefinition,
+    state: Option<String>,
+    ducked: bool,
+
+    /// Music layers by sound id, with their volume parameters. Empty until the music starts.
+    layers: Vec<(String, Option<ParameterHandle>)>,
+}
+
+/// Looping positional sound that follows an entity.
+struct Emitter {
+    volume: f64,
+    panning: f64,
+
+    /// `None` for the silent backend, or when the sound could not be played.
+    instance: Option<InstanceHandle>,
 }
 
-struct Sounds {
-    music: SoundHandle,
-    jump: SoundHandle,
-    splat: SoundHandle,
+/// A playing sound, oldest first.
+struct Voice {
+    id: String,
+    priority: u8,
+    duck: bool,
+
+    /// `None` for the silent backend, where voices end on the next update.
+    instance: Option<InstanceHandle>,
 }
 
 impl Player {
-    pub(crate) fn new() -> Result<Self> {
-        let mut manager = AudioManager::new(AudioManagerSettings::default())?;
+    /// Create a player on the default audio device, or a silent player when there is none.
+    pub(crate) fn new(volume: &Volume) -> Self {
+        let bank = load_bank(SOUND_BANK);
 
-        let music = Cursor::new(include_bytes!(
-            "../cc0/01_-_A.T.M.O.M._-_Nochnoe_Dykhanie_Taigi.ogg"
-        ));
-        let music_settings = SoundSettings {
-            default_loop_start: Some(0.0),
-            ..SoundSettings::default()
+        match Kira::new(&bank) {
+            Ok(kira) => {
+                let mut player = Self::with_backend(bank, Backend::Kira(Box::new(kira)));
+                player.set_volume(volume);
+                player
+            }
+            Err(err) => {
+                warn!("Audio is disabled: {}", err);
+                Self::null()
+            }
+        }
+    }
+
+    /// Create a silent player.
+    pub(crate) fn null() -> Self {
+        let backend = Backend::Null(Null::default());
+
+        Self::with_backend(load_bank(SOUND_BANK), backend)
+    }
+
+    fn with_backend(bank: HashMap<String, SoundDefinition>, backend: Backend) -> Self {
+        let music = Music {
+            definition: load_music(MUSIC, &bank),
+            state: None,
+            ducked: false,
+            layers: Vec::new(),
         };
-        let sound = sound::Sound::from_ogg_reader(music, music_settings)?;
-        let music = manager.add_sound(sound)?;
 
-        let jump = Cursor::new(include_bytes!("../assets/jump.ogg"));
-        let sound = sound::Sound::from_ogg_reader(jump, SoundSettings::default())?;
-        let jump = manager.add_sound(sound)?;
+        Self {
+            bank,
+            backend,
+            voices: Vec::new(),
+            music,
+            emitters: HashMap::new(),
+            now: Duration::ZERO,
+            last_played: HashMap::new(),
+        }
+    }
 
-        let splat = Cursor::new(include_bytes!("../assets/splat.ogg"));
-        let sound = sound::Sound::from_ogg_reader(splat, SoundSettings::default())?;
-        let splat = manager.add_sound(sound)?;
+    pub(crate) fn set_volume(&mut self, volume: &Volume) {
+        if let Backend::Kira(kira) = &mut self.backend {
+            if let Err(err) = kira.set_volume(volume) {
+                warn!("Failed to set volume: {}", err);
+            }
+        }
+    }
 
-        let sounds = Sounds { music, jump, splat };
-        let player = Self {
-            _manager: manager,
-            sounds,
+    /// Release finished voices. Call once per tick, before playing sounds.
+    pub(crate) fn update(&mut self, now: Duration) {
+        self.now = now;
+        self.voices.retain(|voice| {
+            voice
+                .instance
+                .as_ref()
+                .is_some_and(|instance| instance.state() != InstanceState::Stopped)
+        });
+
+        let ducked = self.voices.iter().any(|voice| voice.duck);
+        if ducked != self.music.ducked {
+            self.music.ducked = ducked;
+            self.fade_music(DUCK_FADE);
+        }
+    }
+
+    /// Start, move and stop emitters to match the entities that have them.
+    ///
+    /// `emitters` yields every emitter entity with its sound id, volume, and offset from the
+    /// listener in screen pixels. Emitters keep playing silently beyond `CUTOFF_DISTANCE`.
+    pub(crate) fn update_emitters<'a>(
+        &mut self,
+        emitters: impl Iterator<Item = (EntityId, &'a str, f32, Vec2)>,
+    ) {
+        let mut stale = self.emitters.keys().copied().collect::<HashSet<_>>();
+
+        for (entity, id, volume, offset) in emitters {
+            stale.remove(&entity);
+
+            let definition = self.bank.get(id);
+            let (distance_volume, panning) = spatialize(offset).unwrap_or((0.0, 0.5));
+            let volume = definition.map_or(0.0, |definition| definition.volume)
+                * f64::from(volume)
+                * distance_volume;
+
+            let emitter = match self.emitters.get_mut(&entity) {
+                Some(emitter) => emitter,
+                None => {
+                    let instance = match (definition, &mut self.backend) {
+                        (None, _) => {
+                            warn!("Unknown sound {}", id);
+                            None
+                        }
+                        (Some(definition), _) if !definition.looping => {
+                            warn!("Emitter sound {} does not loop", id);
+                            None
+                        }
+                        (Some(_), Backend::Kira(kira)) => {
+                            match kira.play_emitter(id, volume, panning) {
+                                Ok(instance) => Some(instance),
+                                Err(err) => {
+                                    warn!("Failed to play sound {}: {}", id, err);
+                                    None
+                                }
+                            }
+                        }
+                        (Some(_), Backend::Null(null)) => {
+                            null.play(id);
+                            None
+                        }
+                    };
+
+                    let emitter = Emitter {
+                        volume,
+                        panning,
+                        instance,
+                    };
+
+                    self.emitters.entry(entity).or_insert(emitter)
+                }
+            };
+
+            let changed = (emitter.volume - volume).abs() > EMITTER_THRESHOLD
+                || (emitter.panning - panning).abs() > EMITTER_THRESHOLD;
+            if changed {
+                emitter.volume = volume;
+                emitter.panning = panning;
+                if let Some(instance) = &mut emitter.instance {
+                    let result = instance
+                        .set_volume(volume)
+                        .and_then(|_| instance.set_panning(panning));
+                    if let Err(err) = result {
+                        warn!("Failed to move sound {}: {}", id, err);
+                    }
+                }
+            }
+        }
+
+        for entity in stale {
+            let emitter = self.emitters.remove(&entity).expect("Needs emitter");
+            if let Some(mut instance) = emitter.instance {
+                let settings = StopInstanceSettings::new().fade_tween(Tween::linear(STEAL_FADE));
+                if let Err(err) = instance.stop(settings) {
+                    warn!("Failed to stop emitter: {}", err);
+                }
+            }
+        }
+    }
+
+    /// Crossfade the music to a state, starting it if necessary.
+    pub(crate) fn set_music(&mut self, state: &str) {
+        if self.music.state.as_deref() == Some(state) {
+            return;
+        }
+        if !self.music.definition.states.contains_key(state) {
+            warn!("Unknown music state {}", state);
+            return;
+        }
+
+        debug!("Music state: {}", state);
+        self.music.state = Some(state.to_string());
+
+        if self.music.layers.is_empty() {
+            self.start_music();
+        } else {
+            self.fade_music(self.music.definition.fade);
+        }
+    }
+
+    fn start_music(&mut self) {
+        for id in self.music.definition.layers() {
+            let volume = self.music_volume(id);
+            let parameter = match &mut self.backend {
+                Backend::Kira(kira) => match kira.play_layer(id, volume) {
+                    Ok(parameter) => Some(parameter),
+                    Err(err) => {
+                        warn!("Failed to play music {}: {}", id, err);
+                        None
+                    }
+                },
+                Backend::Null(null) => {
+                    null.play(id);
+                    None
+                }
+            };
+
+            self.music.layers.push((id.to_string(), parameter));
+        }
+    }
+
+    fn fade_music(&mut self, fade: f64) {
+        let volumes = self
+            .music
+            .layers
+            .iter()
+            .map(|(id, _)| self.music_volume(id))
+            .collect::<Vec<_>>();
+
+        for ((id, parameter), volume) in self.music.layers.iter_mut().zip(volumes) {
+            if let Some(parameter) = parameter {
+                if let Err(err) = parameter.set(volume, Tween::linear(fade)) {
+                    warn!("Failed to fade music {}: {}", id, err);
+                }
+            }
+        }
+    }
+
+    /// Target volume of a music layer in the current state.
+    fn music_volume(&self, id: &str) -> f64 {
+        let music = &self.music;
+        let volume = music
+            .state
+            .as_ref()
+            .and_then(|state| music.definition.states[state].get(id))
+            .copied()
+            .unwrap_or(0.0);
+        let duck = if music.ducked {
+            music.definition.duck
+        } else {
+            1.0
+        };
+
+        self.bank[id].volume * volume * duck
+    }
+
+    /// Play a sound from the sound bank at an offset from the listener, in screen pixels.
+    ///
+    /// Sounds are attenuated and panned by their offset, and culled beyond `CUTOFF_DISTANCE`.
+    pub(crate) fn play_at(&mut self, id: &str, offset: Vec2) {
+        match spatialize(offset) {
+            Some((volume, panning)) => self.play_with(id, volume, panning),
+            None => debug!("Sound {} is out of range", id),
+        }
+    }
+
+    fn play_with(&mut self, id: &str, volume: f64, panning: f64) {
+        let definition = match self.bank.get(id) {
+            Some(definition) => definition,
+            None => {
+                warn!("Unknown sound {}", id);
+                return;
+            }
+        };
+
+        let min_interval = Duration::from_secs_f64(definition.min_interval);
+        if let Some(&last) = self.last_played.get(id) {
+            if self.now < last + min_interval {
+                debug!("Sound {} was played too recently", id);
+                return;
+            }
+        }
+
+        let instances = self.voices.iter().filter(|voice| voice.id == id).count();
+        if instances >= definition.max_instances {
+            debug!("Too many instances of sound {}", id);
+            return;
+        }
+
+        if self.voices.len() >= MAX_VOICES {
+            let victim = self
+                .voices
+                .iter()
+                .enumerate()
+                .filter(|(_, voice)| voice.priority <= definition.priority)
+                .min_by_key(|(_, voice)| voice.priority)
+                .map(|(index, _)| index);
+
+            match victim {
+                Some(index) => {
+                    let voice = self.voices.remove(index);
+                    debug!("Sound {} stole a voice from {}", id, voice.id);
+                    if let Some(mut instance) = voice.instance {
+                        let settings =
+                            StopInstanceSettings::new().fade_tween(Tween::linear(STEAL_FADE));
+                        if let Err(err) = instance.stop(settings) {
+                            warn!("Failed to stop sound {}: {}", voice.id, err);
+                        }
+                    }
+                }
+                None => {
+                    debug!("No free voice for sound {}", id);
+                    return;
+                }
+            }
+        }
+
+        let instance = match &mut self.backend {
+            Backend::Kira(kira) => {
+                let sound = kira.sounds.get_mut(id).expect("Needs sound");
+                let settings = InstanceSettings::default()
+                    .volume(definition.volume * volume)
+                    .panning(panning)
+                    .track(kira.sfx.id());
+                match sound.play(settings) {
+                    Ok(instance) => Some(instance),
+                    Err(err) => {
+                        warn!("Failed to play sound {}: {}", id, err);
+                        return;
+                    }
+                }
+            }
+            Backend::Null(null) => {
+                null.play(id);
+                #[cfg(test)]
+                if definition.duck {
+                    null.ducked_by.push(id.to_string());
+                }
+                None
+            }
         };
 
-        Ok(player)
+        self.voices.push(Voice {
+            id: id.to_string(),
+            priority: definition.priority,
+            duck: definition.duck,
+            instance,
+        });
+        self.last_played.insert(id.to_string(), self.now);
+
+        if definition.duck && !self.music.ducked {
+            self.music.ducked = true;
+            self.fade_music(DUCK_FADE);
+        }
     }
 
-    pub(crate) fn music(&mut self) -> Result<()> {
-        self.sounds.music.play(InstanceSettings::default())?;
+    /// Sounds requested from a silent player, in order.
+    #[cfg(test)]
+    pub(crate) fn requested(&self) -> &[String] {
+        match &self.backend {
+            Backend::Kira(_) => &[],
+            Backend::Null(null) => &null.requested,
+        }
+    }
+
+    /// Sounds that ducked the music on a silent player, in order.
+    #[cfg(test)]
+    pub(crate) fn ducked_by(&self) -> &[String] {
+        match &self.backend {
+            Backend::Kira(_) => &[],
+            Backend::Null(null) => &null.ducked_by,
+        }
+    }
+}
+
+impl Null {
+    fn play(&mut self, _id: &str) {
+        #[cfg(test)]
+        self.requested.push(_id.to_string());
+    }
+}
+
+impl Kira {
+    fn new(bank: &HashMap<String, SoundDefinition>) -> Result<Self> {
+        let mut manager = AudioManager::new(AudioManagerSettings::default())?;
+        let music = manager.add_sub_track(SubTrackSettings::default())?;
+        let sfx = manager.add_sub_track(SubTrackSettings::default())?;
+
+        let mut sounds = HashMap::new();
+        for (id, definition) in bank {
+            let settings = SoundSettings {
+                default_loop_start: definition.looping.then_some(0.0),
+                ..SoundSettings::default()
+            };
+            let data = Cursor::new(sound_file(&definition.file));
+            let sound = sound::Sound::from_ogg_reader(data, settings)?;
+            sounds.insert(id.clone(), manager.add_sound(sound)?);
+        }
+
+        Ok(Self {
+            manager,
+            sounds,
+            music,
+            sfx,
+        })
+    }
+
+    fn set_volume(&mut self, volume: &Volume) -> Result<()> {
+        let mut master = self.manager.main_track();
+        master.set_volume(volume.master.volume())?;
+        self.music.set_volume(volume.music.volume())?;
+        self.sfx.set_volume(volume.sfx.volume())?;
 
         Ok(())
     }
 
-    pub(crate) fn jump(&mut self) {
-        self.sounds.jump.play(InstanceSettings::default()).ok();
+    fn play_emitter(&mut self, id: &str, volume: f64, panning: f64) -> Result<InstanceHandle> {
+        let settings = InstanceSettings::default()
+            .volume(volume)
+            .panning(panning)
+            .track(self.sfx.id());
+        let sound = self.sounds.get_mut(id).expect("Needs sound");
+
+        Ok(sound.play(settings)?)
+    }
+
+    /// Play a music layer with its volume controlled by a parameter.
+    fn play_layer(&mut self, id: &str, volume: f64) -> Result<ParameterHandle> {
+        let parameter = self
+            .manager
+            .add_parameter(ParameterSettings::new().value(volume))?;
+        let volume = Value::Parameter(parameter.id(), Mapping::default());
+        let settings = InstanceSettings::default()
+            .volume(volume)
+            .track(self.music.id());
+        let sound = self.sounds.get_mut(id).expect("Needs sound");
+        sound.play(settings)?;
+
+        Ok(parameter)
+    }
+}
+
+// The shipped manifests are also checked by `build.rs`, so these only fail for test data
+fn load_bank(json: &str) -> HashMap<String, SoundDefinition> {
+    let files = SOUND_FILES
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+
+    bank::parse_bank(json, &files).unwrap_or_else(|err| panic!("{}", err))
+}
+
+fn load_music(json: &str, bank: &HashMap<String, SoundDefinition>) -> MusicDefinition {
+    bank::parse_music(json, bank).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Volume and panning for a sound at an offset from the listener, or `None` when it is too far away
+/// to be heard.
+fn spatialize(offset: Vec2) -> Option<(f64, f64)> {
+    let distance = offset.mag();
+    if distance > CUTOFF_DISTANCE {
+        return None;
+    }
+
+    let falloff = (distance - FULL_VOLUME_DISTANCE) / (CUTOFF_DISTANCE - FULL_VOLUME_DISTANCE);
+    let volume = 1.0 - falloff.max(0.0);
+    let side = (offset.x / (WIDTH as f32 / 2.0)).clamp(-1.0, 1.0);
+    let panning = 0.5 + side * MAX_PANNING;
+
+    Some((f64::from(volume), f64::from(panning)))
+}
+
+fn sound_file(path: &str) -> &'static [u8] {
+    SOUND_FILES
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, data)| *data)
+        .unwrap_or_else(|| panic!("Sound file {} does not exist", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::{self, Random};
+    use crate::world::load_world;
+    use shipyard::{AllStoragesViewMut, IntoIter, View, World};
+    use std::iter;
+
+    #[test]
+    fn test_null_player() {
+        let mut player = Player::null();
+
+        player.play_at("music", Vec2::zero());
+        player.play_at("jump", Vec2::zero());
+        player.play_at("whistle", Vec2::zero());
+        player.play_at("splat", Vec2::zero());
+
+        assert_eq!(player.requested(), ["music", "jump", "splat"]);
+    }
+
+    #[test]
+    fn test_spatialize() {
+        assert_eq!(spatialize(Vec2::zero()), Some((1.0, 0.5)));
+        assert_eq!(spatialize(Vec2::new(0.0, -60.0)), Some((1.0, 0.5)));
+
+        // Sounds pan toward the side they come from
+        let (_, left) = spatialize(Vec2::new(-40.0, 0.0)).unwrap();
+        let (_, right) = spatialize(Vec2::new(200.0, 0.0)).unwrap();
+        assert!(left < 0.5);
+        assert!((right - 0.9).abs() < 1e-6);
+
+        // And fade out with distance
+        let (near, _) = spatialize(Vec2::new(0.0, 100.0)).unwrap();
+        let (far, _) = spatialize(Vec2::new(0.0, 200.0)).unwrap();
+        assert!(1.0 > near && near > far && far > 0.0);
+        assert_eq!(spatialize(Vec2::new(0.0, 250.0)), None);
+    }
+
+    #[test]
+    fn test_culled_sounds() {
+        let mut player = Player::null();
+
+        player.play_at("splat", Vec2::new(-30.0, 10.0));
+        player.play_at("splat", Vec2::new(300.0, 0.0));
+
+        assert_eq!(player.requested(), ["splat"]);
+    }
+
+    #[test]
+    fn test_voice_limits() {
+        let mut player = Player {
+            bank: load_bank(
+                r#"{
+                    "jump": { "file": "assets/jump.ogg", "max_instances": 2, "min_interval": 0.1 },
+                    "splat": { "file": "assets/splat.ogg", "max_instances": 2 }
+                }"#,
+            ),
+            ..Player::null()
+        };
+
+        // Instances are capped per sound
+        for _ in 0..3 {
+            player.play_at("splat", Vec2::zero());
+        }
+        assert_eq!(player.requested(), ["splat", "splat"]);
+
+        // Silent voices end on the next update, but sounds can't retrigger too quickly
+        player.update(Duration::from_millis(50));
+        player.play_at("jump", Vec2::zero());
+        player.play_at("splat", Vec2::zero());
+        player.update(Duration::from_millis(100));
+        player.play_at("jump", Vec2::zero());
+        player.update(Duration::from_millis(200));
+        player.play_at("jump", Vec2::zero());
+        assert_eq!(player.requested()[2..], ["jump", "splat", "jump"]);
+    }
+
+    #[test]
+    fn test_voice_stealing() {
+        let mut player = Player {
+            bank: load_bank(
+                r#"{
+                    "jump": { "file": "assets/jump.ogg", "max_instances": 20 },
+                    "splat": { "file": "assets/splat.ogg", "max_instances": 20, "priority": 1 }
+                }"#,
+            ),
+            ..Player::null()
+        };
+
+        for _ in 0..MAX_VOICES {
+            player.play_at("jump", Vec2::zero());
+        }
+
+        // Higher priority sounds steal the oldest voice
+        player.play_at("splat", Vec2::zero());
+        assert_eq!(player.voices.len(), MAX_VOICES);
+        assert_eq!(player.voices[MAX_VOICES - 1].id, "splat");
+        assert_eq!(player.requested().len(), MAX_VOICES + 1);
+
+        // Equal priority steals lower priority voices first
+        player.play_at("jump", Vec2::zero());
+        assert_eq!(
+            player
+                .voices
+                .iter()
+                .filter(|voice| voice.id == "splat")
+                .count(),
+            1
+        );
+
+        // Lower priority sounds are dropped when only higher priority voices are left
+        for _ in 0..MAX_VOICES {
+            player.play_at("splat", Vec2::zero());
+        }
+        assert!(player.voices.iter().all(|voice| voice.id == "splat"));
+        player.play_at("jump", Vec2::zero());
+        assert_eq!(player.requested().last().unwrap(), "splat");
+    }
+
+    #[test]
+    fn test_music() {
+        let mut player = Player::null();
+        player.music.definition = load_music(
+            r#"{
+                "fade": 1.0,
+                "duck": 0.5,
+                "states": {
+                    "calm": { "music": 0.5 },
+                    "threat": { "music": 1.0 }
+                }
+            }"#,
+            &player.bank,
+        );
+        assert_eq!(player.music_volume("music"), 0.0);
+
+        // Layers start with the first state, and keep playing when it changes
+        player.set_music("calm");
+        assert_eq!(player.music_volume("music"), 0.5);
+        player.set_music("threat");
+        player.set_music("outro");
+        assert_eq!(player.music_volume("music"), 1.0);
+        assert_eq!(player.requested(), ["music"]);
+
+        // Ducking lasts while the sound plays
+        player.play_at("jump", Vec2::zero());
+        assert_eq!(player.music_volume("music"), 1.0);
+        player.play_at("splat", Vec2::zero());
+        assert_eq!(player.music_volume("music"), 1.0);
+        player.play_at("summon", Vec2::zero());
+        assert_eq!(player.music_volume("music"), 0.5);
+        player.update(Duration::ZERO);
+        assert_eq!(player.music_volume("music"), 1.0);
+    }
+
+    #[test]
+    fn test_music_crossfade() {
+        let mut player = Player::null();
+        player.set_music("calm");
+        assert_eq!(player.requested(), ["heartbeat", "music"]);
+        assert_eq!(player.music_volume("heartbeat"), 0.0);
+
+        // The music fades down as the heartbeat fades up, and back again
+        let calm = player.music_volume("music");
+        player.set_music("threat");
+        assert!(player.music_volume("music") < calm);
+        assert!(player.music_volume("heartbeat") > 0.0);
+
+        player.set_music("calm");
+        assert_eq!(player.music_volume("music"), calm);
+        assert_eq!(player.music_volume("heartbeat"), 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Music layer jump does not loop")]
+    fn test_load_music_one_shot_layer() {
+        let bank = load_bank(SOUND_BANK);
+        load_music(
+            r#"{ "fade": 1.0, "duck": 1.0, "states": { "calm": { "jump": 1.0 } } }"#,
+            &bank,
+        );
+    }
+
+    #[test]
+    fn test_emitters() {
+        let mut player = Player {
+            bank: load_bank(
+                r#"{
+                    "drone": { "file": "assets/jump.ogg", "volume": 0.5, "loop": true },
+                    "splat": { "file": "assets/splat.ogg" }
+                }"#,
+            ),
+            ..Player::null()
+        };
+        let mut world = World::new();
+        let near = world.add_entity(());
+        let far = world.add_entity(());
+        let one_shot = world.add_entity(());
+
+        let emitters = [
+            (near, "drone", 1.0, Vec2::new(-40.0, 0.0)),
+            (far, "drone", 0.5, Vec2::new(0.0, 300.0)),
+            (one_shot, "splat", 1.0, Vec2::zero()),
+        ];
+        player.update_emitters(emitters.iter().copied());
+        assert_eq!(player.requested(), ["drone", "drone"]);
+        assert_eq!(player.emitters[&near].volume, 0.5);
+        assert!(player.emitters[&near].panning < 0.5);
+        assert_eq!(player.emitters[&far].volume, 0.0);
+
+        // Emitters keep playing as they move, and stop when their entity is gone
+        player.update_emitters(iter::once((far, "drone", 0.5, Vec2::zero())));
+        assert_eq!(player.requested().len(), 2);
+        assert_eq!(player.emitters[&far].volume, 0.25);
+        assert_eq!(player.emitters.len(), 1);
+    }
+
+    #[test]
+    fn test_load_bank() {
+        let bank = load_bank(
+            r#"{
+                "jump": { "file": "assets/jump.ogg", "volume": 0.5 },
+                "music": { "file": "assets/splat.ogg", "loop": true, "max_instances": 1 }
+            }"#,
+        );
+
+        let jump = &bank["jump"];
+        assert_eq!(jump.volume, 0.5);
+        assert!(!jump.looping);
+        assert_eq!(jump.max_instances, 8);
+
+        let music = &bank["music"];
+        assert!(music.looping);
+        assert_eq!(music.max_instances, 1);
+    }
+
+    #[test]
+    fn test_map_emitters() {
+        let world = World::default();
+        load_world(
+            world.borrow::<AllStoragesViewMut>().unwrap(),
+            Random::from_seed(0),
+        );
+
+        let bank = load_bank(SOUND_BANK);
+        let emitters = world.borrow::<View<component::Emitter>>().unwrap();
+        assert!((&emitters).iter().count() > 0);
+        for emitter in (&emitters).iter() {
+            assert!(bank[&emitter.sound].looping, "{}", emitter.sound);
+        }
     }
 
-    pub(crate) fn splat(&mut self) {
-        self.sounds.splat.play(InstanceSettings::default()).ok();
+    #[test]
+    #[should_panic(expected = "Sound file assets/missing.ogg does not exist")]
+    fn test_load_bank_missing_file() {
+        load_bank(r#"{ "missing": { "file": "assets/missing.ogg" } }"#);
     }
 }
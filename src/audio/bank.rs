@@ -0,0 +1,98 @@
+//! Sound bank and music manifests.
+//!
+//! `build.rs` includes this module to check the shipped manifests, so it only depends on serde.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+pub(crate) struct SoundDefinition {
+    pub(crate) file: String,
+    #[serde(default = "default_volume")]
+    pub(crate) volume: f64,
+    #[serde(default, rename = "loop")]
+    pub(crate) looping: bool,
+    #[serde(default = "default_max_instances")]
+    pub(crate) max_instances: usize,
+
+    /// Seconds before the sound can be played again.
+    #[serde(default)]
+    pub(crate) min_interval: f64,
+
+    /// Voices may only be stolen by sounds of the same or higher priority.
+    #[serde(default)]
+    pub(crate) priority: u8,
+    #[serde(default)]
+    pub(crate) duck: bool,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MusicDefinition {
+    pub(crate) fade: f64,
+    pub(crate) duck: f64,
+
+    /// Layer volumes by state.
+    pub(crate) states: HashMap<String, HashMap<String, f64>>,
+}
+
+impl MusicDefinition {
+    /// Every layer in any state, sorted by id.
+    pub(crate) fn layers(&self) -> Vec<&str> {
+        let mut layers = self
+            .states
+            .values()
+            .flat_map(|layers| layers.keys())
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        layers.sort_unstable();
+        layers.dedup();
+
+        layers
+    }
+}
+
+fn default_volume() -> f64 {
+    1.0
+}
+
+fn default_max_instances() -> usize {
+    8
+}
+
+/// Parse the sound bank, checking that every sound plays one of `files`.
+pub(crate) fn parse_bank(
+    json: &str,
+    files: &[&str],
+) -> Result<HashMap<String, SoundDefinition>, String> {
+    let bank: HashMap<String, SoundDefinition> =
+        serde_json::from_str(json).map_err(|err| format!("Invalid sound bank: {}", err))?;
+
+    for definition in bank.values() {
+        if !files.contains(&definition.file.as_str()) {
+            return Err(format!("Sound file {} does not exist", definition.file));
+        }
+    }
+
+    Ok(bank)
+}
+
+/// Parse the music, checking that every layer is a looping sound in the `bank`.
+pub(crate) fn parse_music(
+    json: &str,
+    bank: &HashMap<String, SoundDefinition>,
+) -> Result<MusicDefinition, String> {
+    let music: MusicDefinition =
+        serde_json::from_str(json).map_err(|err| format!("Invalid music: {}", err))?;
+
+    for id in music.layers() {
+        match bank.get(id) {
+            None => return Err(format!("Music layer {} is not in the sound bank", id)),
+            Some(definition) if !definition.looping => {
+                return Err(format!("Music layer {} does not loop", id));
+            }
+            Some(_) => (),
+        }
+    }
+
+    Ok(music)
+}
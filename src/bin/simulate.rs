@@ -0,0 +1,22 @@
+#![deny(clippy::all)]
+#![forbid(unsafe_code)]
+
+use anyhow::Result;
+use sombervale::{Options, Simulation};
+
+// One minute of game time
+const DEFAULT_TICKS: u64 = 3600;
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    let options = Options::from_args()?;
+    let mut simulation = Simulation::new(&options)?;
+    for _ in 0..options.ticks.unwrap_or(DEFAULT_TICKS) {
+        simulation.step();
+    }
+
+    println!("{}", simulation.summary());
+
+    Ok(())
+}
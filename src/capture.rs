@@ -0,0 +1,219 @@
+use crate::{HEIGHT, WIDTH};
+use anyhow::Result;
+use log::{error, info, warn};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// Largest upscale factor; GIF dimensions must also fit in 16 bits
+const MAX_SCALE: u32 = 16;
+
+// Stop recording when the frames reach this size, about a minute at 60 fps
+const MAX_RECORDING_BYTES: usize = 256 * 1024 * 1024;
+
+// NeuQuant sampling factor for GIF palettes; 1 is best quality, 30 is fastest
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+/// Screenshot and GIF capture of the rendered frame buffer.
+pub(crate) struct Capture {
+    scale: u32,
+    recording: Option<Vec<(Instant, Vec<u8>)>>,
+}
+
+impl Capture {
+    /// Captured images are upscaled by the integer `scale` factor, up to `MAX_SCALE`.
+    pub(crate) fn new(scale: u32) -> Self {
+        let clamped = scale.clamp(1, MAX_SCALE);
+        if clamped != scale {
+            warn!("Capture scale {} is out of range, using {}", scale, clamped);
+        }
+
+        Self {
+            scale: clamped,
+            recording: None,
+        }
+    }
+
+    /// Create a capture from the `SOMBERVALE_CAPTURE_SCALE` environment variable.
+    pub(crate) fn from_env() -> Self {
+        let scale = std::env::var("SOMBERVALE_CAPTURE_SCALE")
+            .ok()
+            .and_then(|scale| scale.parse().ok())
+            .unwrap_or(1);
+
+        Self::new(scale)
+    }
+
+    /// Write the frame to a new PNG file in the current directory.
+    pub(crate) fn screenshot(&self, frame: &[u8]) -> Result<PathBuf> {
+        let path = file_name("png");
+        let mut image = upscale(frame, self.scale);
+        opaque(&mut image);
+        write_png(&path, &image, WIDTH * self.scale, HEIGHT * self.scale)?;
+        info!("Saved screenshot to {}", path.display());
+
+        Ok(path)
+    }
+
+    /// Start recording, or stop recording and write the frames to a new GIF file.
+    pub(crate) fn toggle_recording(&mut self) -> Option<PathBuf> {
+        match self.recording.take() {
+            Some(frames) => Some(self.write_gif(frames)),
+            None => {
+                info!("Recording started");
+                self.recording = Some(Vec::new());
+
+                None
+            }
+        }
+    }
+
+    /// Append the frame to the recording, if one is in progress.
+    pub(crate) fn record(&mut self, frame: &[u8]) {
+        if let Some(frames) = self.recording.as_mut() {
+            frames.push((Instant::now(), frame.to_vec()));
+
+            if frames.len() * frame.len() >= MAX_RECORDING_BYTES {
+                let elapsed = frames[0].0.elapsed();
+                warn!(
+                    "Recording reached {} MiB after {:.1} seconds, stopping",
+                    MAX_RECORDING_BYTES / 1024 / 1024,
+                    elapsed.as_secs_f32()
+                );
+                self.toggle_recording();
+            }
+        }
+    }
+
+    /// Encode the frames to a new GIF file on a worker thread, so the game keeps running.
+    fn write_gif(&self, frames: Vec<(Instant, Vec<u8>)>) -> PathBuf {
+        let path = file_name("gif");
+        let scale = self.scale;
+        let gif_path = path.clone();
+        thread::spawn(move || {
+            if let Err(err) = encode_gif(&gif_path, &frames, scale) {
+                error!("Recording failed: {}", err);
+            }
+        });
+        info!("Recording stopped, encoding {}", path.display());
+
+        path
+    }
+}
+
+fn encode_gif(path: &Path, frames: &[(Instant, Vec<u8>)], scale: u32) -> Result<()> {
+    // The scale is clamped to `MAX_SCALE`, so this fits
+    let width = (WIDTH * scale) as u16;
+    let height = (HEIGHT * scale) as u16;
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = gif::Encoder::new(file, width, height, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    // Each frame is displayed until the next one was captured
+    let mut timestamps = frames.iter().map(|(time, _)| *time).skip(1);
+    for (time, frame) in frames {
+        let next = timestamps.next().unwrap_or(*time);
+        let mut image = upscale(frame, scale);
+        opaque(&mut image);
+
+        let mut frame = gif::Frame::from_rgba_speed(width, height, &mut image, GIF_QUANTIZE_SPEED);
+        frame.delay = ((next - *time).as_millis() / 10).max(1) as u16;
+        encoder.write_frame(&frame)?;
+    }
+
+    info!(
+        "Saved {} frame recording to {}",
+        frames.len(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// The frame buffer alpha channel is meaningless once rendered, so make every pixel opaque.
+fn opaque(image: &mut [u8]) {
+    for pixel in image.chunks_exact_mut(4) {
+        pixel[3] = 0xff;
+    }
+}
+
+pub(crate) fn write_png(path: &Path, data: &[u8], width: u32, height: u32) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(data)?;
+
+    Ok(())
+}
+
+/// Nearest-neighbor upscale of a full frame.
+fn upscale(frame: &[u8], scale: u32) -> Vec<u8> {
+    if scale == 1 {
+        return frame.to_vec();
+    }
+
+    let scale = scale as usize;
+    let row_size = WIDTH as usize * 4;
+    let mut image = Vec::with_capacity(frame.len() * scale * scale);
+
+    for row in frame.chunks_exact(row_size) {
+        let start = image.len();
+        for pixel in row.chunks_exact(4) {
+            for _ in 0..scale {
+                image.extend_from_slice(pixel);
+            }
+        }
+        for _ in 1..scale {
+            image.extend_from_within(start..start + row_size * scale);
+        }
+    }
+
+    image
+}
+
+fn file_name(extension: &str) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or_default();
+
+    PathBuf::from(format!("sombervale-{}.{}", timestamp, extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale() {
+        assert!(WIDTH.max(HEIGHT) * MAX_SCALE <= u32::from(u16::MAX));
+
+        assert_eq!(Capture::new(0).scale, 1);
+        assert_eq!(Capture::new(3).scale, 3);
+        assert_eq!(Capture::new(u32::MAX).scale, MAX_SCALE);
+    }
+
+    #[test]
+    fn test_upscale() {
+        let frame = (0..WIDTH * HEIGHT)
+            .flat_map(|i| [i as u8, (i >> 8) as u8, 0, 0xff])
+            .collect::<Vec<_>>();
+        let image = upscale(&frame, 3);
+        let width = WIDTH as usize * 3;
+
+        assert_eq!(image.len(), frame.len() * 9);
+
+        // Every 3x3 block matches the source pixel
+        for (i, pixel) in frame.chunks_exact(4).enumerate() {
+            let (x, y) = (i % WIDTH as usize * 3, i / WIDTH as usize * 3);
+            for (dx, dy) in [(0, 0), (2, 0), (0, 2), (2, 2)] {
+                let index = ((y + dy) * width + x + dx) * 4;
+                assert_eq!(&image[index..index + 4], pixel);
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+
+/// Length of a simulation step (60 Hz).
+pub(crate) const TIMESTEP: Duration = Duration::from_nanos(16_666_667);
+
+// Longest wall-clock time accumulated per tick, so a stall does not take many steps to catch up
+const MAX_TICK: Duration = Duration::from_millis(250);
+
+/// Simulated game time.
+///
+/// Time only moves forward when the clock is stepped or advanced, so everything that reads it can
+/// be tested by advancing the clock manually. Times are measured from when the clock was created.
+pub(crate) struct GameClock {
+    now: Duration,
+    delta: Duration,
+    ticks: u64,
+    scale: f32,
+    last_tick: Option<Instant>,
+
+    /// Wall-clock time that has not been simulated yet.
+    accumulator: Duration,
+}
+
+impl GameClock {
+    /// Simulated time passes `scale` times as fast as wall-clock time.
+    pub(crate) fn new(scale: f32) -> Self {
+        Self {
+            now: Duration::ZERO,
+            delta: Duration::ZERO,
+            ticks: 0,
+            scale: scale.max(0.0),
+            last_tick: None,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// Create a clock from the `SOMBERVALE_TIME_SCALE` environment variable.
+    pub(crate) fn from_env() -> Self {
+        let scale = std::env::var("SOMBERVALE_TIME_SCALE")
+            .ok()
+            .and_then(|scale| scale.parse().ok())
+            .unwrap_or(1.0);
+
+        Self::new(scale)
+    }
+
+    /// Current simulated time.
+    pub(crate) fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// Simulated time that passed during the last update.
+    pub(crate) fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    /// Number of times the clock has advanced, i.e. the current simulation tick.
+    pub(crate) fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Simulated time since `since`.
+    pub(crate) fn elapsed(&self, since: Duration) -> Duration {
+        self.now.saturating_sub(since)
+    }
+
+    /// Advance simulated time by `delta`.
+    pub(crate) fn advance(&mut self, delta: Duration) {
+        self.delta = delta;
+        self.now += delta;
+        self.ticks += 1;
+    }
+
+    /// Accumulate the scaled wall-clock time since the last tick, to be simulated by `step`.
+    pub(crate) fn tick(&mut self) {
+        let now = Instant::now();
+        let real = self.last_tick.map_or(Duration::ZERO, |last| now - last);
+        self.last_tick = Some(now);
+
+        self.accumulator += real.min(MAX_TICK).mul_f32(self.scale);
+    }
+
+    /// Advance by one `TIMESTEP` if enough time has accumulated.
+    ///
+    /// Returns `false` when the remaining time is less than a step, and the simulation should wait
+    /// for the next tick.
+    pub(crate) fn step(&mut self) -> bool {
+        if self.accumulator < TIMESTEP {
+            return false;
+        }
+
+        self.accumulator -= TIMESTEP;
+        self.advance(TIMESTEP);
+
+        true
+    }
+
+    /// How far between the previous and current step to render, from 0.0 to 1.0.
+    pub(crate) fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / TIMESTEP.as_secs_f32()
+    }
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_advance() {
+        let mut clock = GameClock::default();
+        let start = clock.now();
+
+        clock.advance(Duration::from_millis(250));
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(clock.delta(), Duration::from_millis(500));
+        assert_eq!(clock.elapsed(start), Duration::from_millis(750));
+
+        // Times in the future have no elapsed time
+        assert_eq!(clock.elapsed(Duration::from_secs(1)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_clock_fixed_steps() {
+        let mut clock = GameClock {
+            accumulator: TIMESTEP * 5 / 2,
+            ..GameClock::default()
+        };
+
+        let mut steps = 0;
+        while clock.step() {
+            assert_eq!(clock.delta(), TIMESTEP);
+            steps += 1;
+        }
+        assert_eq!(steps, 2);
+        assert_eq!(clock.ticks(), 2);
+        assert_eq!(clock.now(), TIMESTEP * 2);
+        assert!((clock.alpha() - 0.5).abs() < 0.001);
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::OnceLock;
+use ultraviolet::{Vec3, Vec4};
+
+/// Linear RGBA color with premultiplied alpha.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct Color(pub(crate) Vec4);
+
+impl Color {
+    pub(crate) fn from_srgba(srgba: [u8; 4]) -> Self {
+        let alpha = srgba[3] as f32 / 255.0;
+        let rgb = Vec3::new(
+            srgb_to_linear(srgba[0]),
+            srgb_to_linear(srgba[1]),
+            srgb_to_linear(srgba[2]),
+        );
+
+        Self(Vec4::new(rgb.x, rgb.y, rgb.z, 1.0) * alpha)
+    }
+
+    pub(crate) fn to_srgba(self) -> [u8; 4] {
+        let alpha = self.0.w.clamp(0.0, 1.0);
+        if alpha <= 0.0 {
+            return [0; 4];
+        }
+
+        let rgb = self.0.xyz() / alpha;
+        [
+            linear_to_srgb(rgb.x),
+            linear_to_srgb(rgb.y),
+            linear_to_srgb(rgb.z),
+            (alpha * 255.0).round() as u8,
+        ]
+    }
+
+    /// Source-over compositing of `self` on top of `dest`.
+    pub(crate) fn over(self, dest: Self) -> Self {
+        Self(self.0 + dest.0 * (1.0 - self.0.w))
+    }
+
+    /// Add the color of `self` to `dest`.
+    pub(crate) fn add(self, dest: Self) -> Self {
+        let rgb = self.0.xyz() + dest.0.xyz();
+        let alpha = self.0.w + dest.0.w;
+
+        Self(Vec4::new(rgb.x, rgb.y, rgb.z, alpha).min_by_component(Vec4::broadcast(1.0)))
+    }
+
+    /// Multiply `dest` by the color of `self`.
+    pub(crate) fn multiply(self, dest: Self) -> Self {
+        // Blend between no change and full multiplication by the source coverage
+        let rgb = dest.0.xyz() * (self.0.xyz() + Vec3::broadcast(1.0 - self.0.w));
+
+        Self(Vec4::new(rgb.x, rgb.y, rgb.z, dest.0.w))
+    }
+}
+
+/// Decode an sRGB channel to linear light.
+pub(crate) fn srgb_to_linear(value: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+
+    let table = TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = decode(i as f32 / 255.0);
+        }
+        table
+    });
+
+    table[value as usize]
+}
+
+/// Encode a linear light channel to sRGB.
+pub(crate) fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (encoded * 255.0).round() as u8
+}
+
+fn decode(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Multiply every pixel in the frame by a linear `tint`, ignoring alpha.
+pub(crate) fn tint(frame: &mut [u8], tint: Vec3) {
+    for pixel in frame.chunks_exact_mut(4) {
+        pixel[0] = linear_to_srgb(srgb_to_linear(pixel[0]) * tint.x);
+        pixel[1] = linear_to_srgb(srgb_to_linear(pixel[1]) * tint.y);
+        pixel[2] = linear_to_srgb(srgb_to_linear(pixel[2]) * tint.z);
+    }
+}
+
+/// Tint that fades the frame to black, where `brightness` is perceived brightness.
+///
+/// Linear light is not perceived linearly, so the brightness is decoded like an sRGB value to
+/// make fades look even over time.
+pub(crate) fn fade(brightness: f32) -> Vec3 {
+    Vec3::broadcast(decode(brightness.clamp(0.0, 1.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_round_trip() {
+        for value in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+        }
+
+        let color = [0x38, 0xb7, 0x64, 0x80];
+        assert_eq!(Color::from_srgba(color).to_srgba(), color);
+    }
+
+    #[test]
+    fn test_fade() {
+        let mut frame = vec![0xff, 0x80, 0x00, 0xff];
+
+        tint(&mut frame, fade(1.0));
+        assert_eq!(frame, [0xff, 0x80, 0x00, 0xff]);
+
+        // Half brightness halves the encoded value of white
+        tint(&mut frame, fade(0.5));
+        assert_eq!(frame, [0x80, 0x3d, 0x00, 0xff]);
+
+        tint(&mut frame, fade(0.0));
+        assert_eq!(frame, [0x00, 0x00, 0x00, 0xff]);
+    }
+}
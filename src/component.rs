@@ -1,15 +1,18 @@
-use crate::animation::Animated;
+use crate::animation::Animator;
 use crate::audio::Player;
+use crate::capture;
 use crate::control;
-use crate::image::Image;
+use crate::image::{Atlas, AtlasFrame, Blend, Flip, Image};
+use crate::light::PointLight;
 use crate::map::Rect;
-use anyhow::Result;
+use crate::replay;
+use crate::settings::Volume;
+use crate::{HEIGHT, WIDTH};
 use getrandom::getrandom;
 use randomize::PCG32;
 use shipyard::EntityId;
-use std::convert::TryInto;
-use std::time::Instant;
-use ultraviolet::{Vec2, Vec3};
+use std::time::Duration;
+use ultraviolet::{Lerp, Vec2, Vec3};
 
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) enum CoordinateSystem {
@@ -20,18 +23,54 @@ pub(crate) enum CoordinateSystem {
     Screen,
 }
 
-pub(crate) struct Outro(pub(crate) Instant, pub(crate) f32);
+/// CPU-side frame buffer rendered by the draw workload.
+pub(crate) struct Frame(pub(crate) Vec<u8>);
+/// Game time when the outro started, and the current opacity.
+pub(crate) struct Outro(pub(crate) Duration, pub(crate) f32);
 pub(crate) struct Random(pub(crate) PCG32);
+/// Random numbers for effects that must not change gameplay, like light flicker.
+pub(crate) struct CosmeticRandom(pub(crate) PCG32);
+#[derive(Default)]
 pub(crate) struct Controls(pub(crate) control::Controls);
-pub(crate) struct UpdateTime(pub(crate) Instant);
 pub(crate) struct Audio(pub(crate) Player);
+pub(crate) struct Capture(pub(crate) capture::Capture);
+pub(crate) struct Replay(pub(crate) replay::Replay);
 pub(crate) struct Position(pub(crate) Vec3, pub(crate) CoordinateSystem);
+/// Position before the latest simulation step, used to interpolate rendering between steps.
+pub(crate) struct PrevPosition(pub(crate) Vec3);
+#[derive(Default)]
 pub(crate) struct Velocity(pub(crate) Vec3);
-pub(crate) struct Animation<A: Animated>(pub(crate) A);
+pub(crate) struct Animation(pub(crate) Animator);
 pub(crate) struct Annihilate(pub(crate) Vec<EntityId>);
 
+/// Totals for the whole session, kept when the world is reloaded.
+#[derive(Default)]
+pub(crate) struct Stats {
+    pub(crate) frogs_summoned: u32,
+    pub(crate) shadows_annihilated: u32,
+    pub(crate) deaths: u32,
+}
+
+pub(crate) struct Light(pub(crate) PointLight);
+
+/// Looping positional sound from the sound bank, e.g. wind or crickets.
+pub(crate) struct Emitter {
+    pub(crate) sound: String,
+    pub(crate) volume: f32,
+}
+
+// Tags for each kind of creature
+pub(crate) struct Jean;
+pub(crate) struct Frog;
+pub(crate) struct Blob;
+pub(crate) struct Fire;
+
+/// Light level for pixels that are not lit by any `Light`.
+pub(crate) struct Ambient(pub(crate) f32);
+
 pub(crate) struct Viewport {
     pub(crate) pos: Vec2,
+    pub(crate) prev_pos: Vec2,
     pub(crate) world_height: f32,
 }
 
@@ -45,29 +84,79 @@ pub(crate) struct Tilemap {
 }
 
 pub(crate) struct Sprite {
-    pub(crate) image: Image,
-    pub(crate) frame_height: isize,
+    pub(crate) atlas: Atlas,
     pub(crate) frame_index: usize,
+    pub(crate) blend: Blend,
+    pub(crate) flip: Flip,
 }
 
 pub(crate) struct Follow {
     pub(crate) entity_id: EntityId,
     pub(crate) direction: Vec3,
+    pub(crate) hopping: bool,
+}
+
+impl Sprite {
+    pub(crate) fn new(atlas: Atlas, blend: Blend) -> Self {
+        Self {
+            atlas,
+            frame_index: 0,
+            blend,
+            flip: Flip::empty(),
+        }
+    }
+
+    /// The current frame, with its pivot mirrored to match the sprite.
+    pub(crate) fn frame(&self) -> AtlasFrame {
+        let mut frame = *self.atlas.frame(self.frame_index);
+        if self.flip.contains(Flip::HORIZONTAL) {
+            frame.pivot.x = frame.size.x - frame.pivot.x;
+        }
+        if self.flip.contains(Flip::VERTICAL) {
+            frame.pivot.y = frame.size.y - frame.pivot.y;
+        }
+
+        frame
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self(vec![0; (WIDTH * HEIGHT * 4) as usize])
+    }
 }
 
 impl Default for Random {
     fn default() -> Self {
-        let mut seed = [0_u8; 16];
+        Self::from_seed(Self::entropy_seed())
+    }
+}
+
+impl Random {
+    /// Create a reproducible random number generator.
+    pub(crate) fn new(seed: u64, inc: u64) -> Self {
+        Self(PCG32::seed(seed, inc))
+    }
+
+    /// Create a reproducible random number generator from a single seed.
+    pub(crate) fn from_seed(seed: u64) -> Self {
+        // Default PCG stream
+        Self::new(seed, 1_442_695_040_888_963_407)
+    }
 
+    /// Seed that is different on every run.
+    pub(crate) fn entropy_seed() -> u64 {
+        let mut seed = [0_u8; 8];
         getrandom(&mut seed).expect("failed to getrandom");
-        let inc = u64::from_ne_bytes(seed[0..8].try_into().unwrap());
-        let seed = u64::from_ne_bytes(seed[8..16].try_into().unwrap());
 
-        Self(PCG32::seed(seed, inc))
+        u64::from_ne_bytes(seed)
+    }
+
+    /// Derive a seed for another generator from this one.
+    pub(crate) fn next_seed(&mut self) -> u64 {
+        (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
     }
-}
 
-impl Random {
     pub(crate) fn next_u32(&mut self) -> u32 {
         self.0.next_u32()
     }
@@ -83,27 +172,20 @@ impl Random {
     }
 }
 
-impl Default for Controls {
-    fn default() -> Self {
-        Self(control::Controls::default())
-    }
-}
-
-impl Default for UpdateTime {
-    fn default() -> Self {
-        Self(Instant::now())
+impl Viewport {
+    /// Viewport to render, between the previous and current simulation step.
+    pub(crate) fn interpolate(&self, alpha: f32) -> Self {
+        Self {
+            pos: self.prev_pos.lerp(self.pos, alpha),
+            prev_pos: self.prev_pos,
+            world_height: self.world_height,
+        }
     }
 }
 
 impl Audio {
-    pub(crate) fn new() -> Result<Self> {
-        Ok(Self(Player::new()?))
-    }
-}
-
-impl Default for Velocity {
-    fn default() -> Self {
-        Self(Vec3::default())
+    pub(crate) fn new(volume: &Volume) -> Self {
+        Self(Player::new(volume))
     }
 }
 
@@ -112,6 +194,7 @@ impl Follow {
         Self {
             entity_id,
             direction: Vec3::default(),
+            hopping: false,
         }
     }
 }
@@ -1,11 +1,17 @@
+use anyhow::anyhow;
 use bitflags::bitflags;
-use num_enum::TryFromPrimitive;
+use log::warn;
+use serde::de::{value::StrDeserializer, IntoDeserializer};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashSet;
 use std::convert::TryFrom;
-use winit::event::{ElementState, KeyboardInput};
+use std::iter;
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
 
-// Key map for Windows and Linux: http://flint.cs.yale.edu/cs422/doc/art-of-asm/pdf/APNDXC.PDF
+// Default key positions for Windows and Linux:
+// http://flint.cs.yale.edu/cs422/doc/art-of-asm/pdf/APNDXC.PDF
 #[cfg(not(target_os = "macos"))]
-#[derive(Copy, Clone, Debug, TryFromPrimitive)]
+#[derive(Copy, Clone, Debug)]
 #[repr(u32)]
 enum KeyMap {
     W = 17,
@@ -16,9 +22,9 @@ enum KeyMap {
     Tab = 15,
 }
 
-// Keymap for macOS: https://bit.ly/3kThGwO
+// Default key positions for macOS: https://bit.ly/3kThGwO
 #[cfg(target_os = "macos")]
-#[derive(Copy, Clone, Debug, TryFromPrimitive)]
+#[derive(Copy, Clone, Debug)]
 #[repr(u32)]
 enum KeyMap {
     W = 13,
@@ -35,48 +41,58 @@ pub(crate) enum Walk {
     Walk(Direction),
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub(crate) enum Power {
     NoInput,
     Use,
     Select,
 }
 
-#[derive(Copy, Clone, Debug, Default)]
-struct Keys {
-    w: bool,
-    a: bool,
-    s: bool,
-    d: bool,
-    tab: bool,
-    space: bool,
+/// Player actions for a single simulation tick.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Actions {
+    pub(crate) walk: Walk,
+    pub(crate) power: Power,
 }
 
-impl Keys {
-    fn update(&mut self, key: KeyboardInput) {
-        match KeyMap::try_from(key.scancode) {
-            Ok(KeyMap::W) => {
-                self.w = key.state == ElementState::Pressed;
-            }
-            Ok(KeyMap::A) => {
-                self.a = key.state == ElementState::Pressed;
-            }
-            Ok(KeyMap::S) => {
-                self.s = key.state == ElementState::Pressed;
-            }
-            Ok(KeyMap::D) => {
-                self.d = key.state == ElementState::Pressed;
-            }
-            Ok(KeyMap::Space) => {
-                self.space = key.state == ElementState::Pressed;
-            }
-            Ok(KeyMap::Tab) => {
-                self.tab = key.state == ElementState::Pressed;
-            }
-            // Ignore everything else
-            _ => {}
-        }
-    }
+/// A key that triggers an action.
+///
+/// Keys are named like winit's `VirtualKeyCode`, e.g. `"Up"` or `"Space"`, and follow the keyboard
+/// layout. Keys given by position, e.g. `"scancode:17"`, are the same physical key on any layout.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) enum Binding {
+    Key(VirtualKeyCode),
+    Scancode(u32),
+}
+
+/// Keys bound to each action, loaded from the `controls` table of the settings file.
+///
+/// ```toml
+/// [controls]
+/// move_up = ["scancode:17", "Up"]
+/// summon_frog = ["Space", "Return"]
+/// ```
+///
+/// Any key bound to an action triggers it. Actions that are not listed keep their default keys:
+/// W A S D by position and the arrow keys to move, Space to summon a frog, and Tab to select a
+/// power. Unknown keys are logged and skipped.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct Bindings {
+    #[serde(deserialize_with = "known_bindings")]
+    pub(crate) move_up: Vec<Binding>,
+    #[serde(deserialize_with = "known_bindings")]
+    pub(crate) move_down: Vec<Binding>,
+    #[serde(deserialize_with = "known_bindings")]
+    pub(crate) move_left: Vec<Binding>,
+    #[serde(deserialize_with = "known_bindings")]
+    pub(crate) move_right: Vec<Binding>,
+    #[serde(deserialize_with = "known_bindings")]
+    pub(crate) summon_frog: Vec<Binding>,
+    #[serde(deserialize_with = "known_bindings")]
+    pub(crate) select_power: Vec<Binding>,
 }
 
 bitflags! {
@@ -94,7 +110,10 @@ bitflags! {
 }
 
 pub(crate) struct Controls {
-    keys: Keys,
+    bindings: Bindings,
+
+    /// Keys that are currently held down, by name and by position.
+    held: HashSet<Binding>,
     prev_walk: Walk,
     current_walk: Walk,
     prev_power: Power,
@@ -102,9 +121,10 @@ pub(crate) struct Controls {
 }
 
 impl Controls {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(bindings: Bindings) -> Self {
         Self {
-            keys: Keys::default(),
+            bindings,
+            held: HashSet::new(),
             prev_walk: Walk::NoInput,
             current_walk: Walk::NoInput,
             prev_power: Power::NoInput,
@@ -112,6 +132,10 @@ impl Controls {
         }
     }
 
+    pub(crate) fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
     pub(crate) fn begining_diagonal(&mut self) -> bool {
         let result = matches!(
             (self.prev_walk, self.current_walk),
@@ -146,59 +170,271 @@ impl Controls {
         }
     }
 
+    /// Capture a key press or release. Actions only change when the next tick is applied.
     pub(crate) fn update(&mut self, key: KeyboardInput) {
-        // Capture all key states
-        self.keys.update(key);
+        let bindings = iter::once(Binding::Scancode(key.scancode))
+            .chain(key.virtual_keycode.map(Binding::Key));
 
-        // Reset actions states
-        self.prev_walk = self.current_walk;
-        self.current_walk = Walk::NoInput;
-        self.prev_power = self.current_power;
-        self.current_power = Power::NoInput;
+        for binding in bindings {
+            if key.state == ElementState::Pressed {
+                self.held.insert(binding);
+            } else {
+                self.held.remove(&binding);
+            }
+        }
+    }
 
-        // Translate key states into actions
-        let mut dir = Direction::from_bits(match self.current_walk {
-            Walk::NoInput => 0,
-            Walk::Walk(dir) => dir.bits,
-        })
-        .expect("No direction to decode");
+    /// Whether any key bound to an action is held down.
+    fn held(&self, bindings: &[Binding]) -> bool {
+        bindings.iter().any(|binding| self.held.contains(binding))
+    }
+
+    /// Translate the current key states into actions.
+    pub(crate) fn actions(&self) -> Actions {
+        let mut actions = Actions::default();
+        let mut dir = Direction::empty();
 
-        if self.keys.w {
+        if self.held(&self.bindings.move_up) {
             dir = (dir - Direction::DOWN) | Direction::UP;
-            self.current_walk = Walk::Walk(dir);
         }
-        if self.keys.a {
+        if self.held(&self.bindings.move_left) {
             dir = (dir - Direction::RIGHT) | Direction::LEFT;
-            self.current_walk = Walk::Walk(dir);
         }
-        if self.keys.s {
+        if self.held(&self.bindings.move_down) {
             dir = (dir - Direction::UP) | Direction::DOWN;
-            self.current_walk = Walk::Walk(dir);
         }
-        if self.keys.d {
+        if self.held(&self.bindings.move_right) {
             dir = (dir - Direction::LEFT) | Direction::RIGHT;
-            self.current_walk = Walk::Walk(dir);
         }
-        if self.keys.space {
-            self.current_power = Power::Use;
+        if self.held(&self.bindings.summon_frog) {
+            actions.power = Power::Use;
         }
-        if self.keys.tab {
-            self.current_power = Power::Select;
+        if self.held(&self.bindings.select_power) {
+            actions.power = Power::Select;
         }
 
         // Never end up with Walk::Walk(0)
-        let dir = match self.current_walk {
-            Walk::NoInput => 0,
-            Walk::Walk(dir) => dir.bits,
-        };
-        if dir == 0 {
-            self.current_walk = Walk::NoInput;
+        if !dir.is_empty() {
+            actions.walk = Walk::Walk(dir);
+        }
+
+        actions
+    }
+
+    /// Start a simulation tick with the given actions.
+    pub(crate) fn apply(&mut self, actions: Actions) {
+        self.prev_walk = self.current_walk;
+        self.current_walk = actions.walk;
+        self.prev_power = self.current_power;
+        self.current_power = actions.power;
+    }
+}
+
+impl Default for Actions {
+    fn default() -> Self {
+        Self {
+            walk: Walk::NoInput,
+            power: Power::NoInput,
         }
     }
 }
 
 impl Default for Controls {
     fn default() -> Self {
-        Self::new()
+        Self::new(Bindings::default())
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let position = |key: KeyMap| Binding::Scancode(key as u32);
+
+        Self {
+            move_up: vec![position(KeyMap::W), Binding::Key(VirtualKeyCode::Up)],
+            move_down: vec![position(KeyMap::S), Binding::Key(VirtualKeyCode::Down)],
+            move_left: vec![position(KeyMap::A), Binding::Key(VirtualKeyCode::Left)],
+            move_right: vec![position(KeyMap::D), Binding::Key(VirtualKeyCode::Right)],
+            summon_frog: vec![position(KeyMap::Space)],
+            select_power: vec![position(KeyMap::Tab)],
+        }
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = anyhow::Error;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        if let Some(scancode) = name.strip_prefix("scancode:") {
+            let scancode = scancode
+                .parse()
+                .map_err(|_| anyhow!("Invalid scancode: {}", name))?;
+
+            return Ok(Self::Scancode(scancode));
+        }
+
+        let deserializer: StrDeserializer<serde::de::value::Error> =
+            name.as_str().into_deserializer();
+        let key = VirtualKeyCode::deserialize(deserializer)
+            .map_err(|_| anyhow!("Unknown key: {}", name))?;
+
+        Ok(Self::Key(key))
+    }
+}
+
+impl Bindings {
+    /// Describe the controls for the intro message, using the first key bound to each action.
+    pub(crate) fn intro(&self) -> String {
+        let first = |bindings: &[Binding]| bindings.first().map(|binding| binding.label());
+
+        let walk = [
+            &self.move_up,
+            &self.move_left,
+            &self.move_down,
+            &self.move_right,
+        ]
+        .iter()
+        .map(|bindings| first(bindings))
+        .collect::<Option<Vec<_>>>();
+        let walk = match walk {
+            Some(keys) => format!("Run away with {}.", keys.join(" ")),
+            None => "Run away!".to_string(),
+        };
+        let summon = match first(&self.summon_frog) {
+            Some(key) => format!("Summon an imaginary frog with {}.", key),
+            None => "Summon an imaginary frog to defend yourself.".to_string(),
+        };
+
+        format!("{} {}", walk, summon)
+    }
+}
+
+impl Binding {
+    /// Short name of the key to show to the player.
+    fn label(&self) -> String {
+        let defaults = [
+            (KeyMap::W, "W"),
+            (KeyMap::A, "A"),
+            (KeyMap::S, "S"),
+            (KeyMap::D, "D"),
+            (KeyMap::Space, "SPACE"),
+            (KeyMap::Tab, "TAB"),
+        ];
+
+        match self {
+            Self::Key(key) => format!("{:?}", key).to_uppercase(),
+            Self::Scancode(scancode) => defaults
+                .iter()
+                .find(|(key, _)| *key as u32 == *scancode)
+                .map(|(_, name)| name.to_string())
+                .unwrap_or_else(|| format!("KEY {}", scancode)),
+        }
+    }
+}
+
+/// Deserialize a list of key names, skipping the ones that are not keys.
+fn known_bindings<'de, D>(deserializer: D) -> Result<Vec<Binding>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let names = Vec::<String>::deserialize(deserializer)?;
+    let bindings = names
+        .into_iter()
+        .filter_map(|name| match Binding::try_from(name) {
+            Ok(binding) => Some(binding),
+            Err(err) => {
+                warn!("Ignoring key binding: {}", err);
+                None
+            }
+        })
+        .collect();
+
+    Ok(bindings)
+}
+
+impl From<Binding> for String {
+    fn from(binding: Binding) -> Self {
+        match binding {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Scancode(scancode) => format!("scancode:{}", scancode),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(controls: &mut Controls, scancode: u32, key: Option<VirtualKeyCode>, pressed: bool) {
+        let state = if pressed {
+            ElementState::Pressed
+        } else {
+            ElementState::Released
+        };
+
+        #[allow(deprecated)]
+        controls.update(KeyboardInput {
+            scancode,
+            state,
+            virtual_keycode: key,
+            modifiers: Default::default(),
+        });
+    }
+
+    #[test]
+    fn test_bindings() {
+        let bindings = Bindings {
+            move_up: vec![Binding::Scancode(100), Binding::Key(VirtualKeyCode::I)],
+            summon_frog: vec![Binding::Key(VirtualKeyCode::Return)],
+            ..Bindings::default()
+        };
+        let mut controls = Controls::new(bindings);
+
+        // Either key triggers the action, until both are released
+        key(&mut controls, 100, None, true);
+        key(&mut controls, 23, Some(VirtualKeyCode::I), true);
+        key(&mut controls, 100, None, false);
+        assert_eq!(controls.actions().walk, Walk::Walk(Direction::UP));
+        key(&mut controls, 23, Some(VirtualKeyCode::I), false);
+        assert_eq!(controls.actions(), Actions::default());
+
+        key(&mut controls, 28, Some(VirtualKeyCode::Return), true);
+        key(&mut controls, 0, Some(VirtualKeyCode::Left), true);
+        let actions = controls.actions();
+        assert_eq!(actions.walk, Walk::Walk(Direction::LEFT));
+        assert_eq!(actions.power, Power::Use);
+    }
+
+    #[test]
+    fn test_binding_names() {
+        for name in ["Up", "Space", "scancode:17"] {
+            let binding = Binding::try_from(name.to_string()).unwrap();
+            assert_eq!(String::from(binding), name);
+        }
+
+        assert!(Binding::try_from("Upp".to_string()).is_err());
+        assert!(Binding::try_from("scancode:-1".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_intro() {
+        let mut bindings = Bindings::default();
+        assert_eq!(
+            bindings.intro(),
+            "Run away with W A S D. Summon an imaginary frog with SPACE."
+        );
+
+        bindings.move_up = vec![Binding::Key(VirtualKeyCode::I)];
+        bindings.summon_frog = vec![Binding::Key(VirtualKeyCode::Return)];
+        assert_eq!(
+            bindings.intro(),
+            "Run away with I A S D. Summon an imaginary frog with RETURN."
+        );
+
+        bindings.move_down.clear();
+        bindings.summon_frog.clear();
+        assert_eq!(
+            bindings.intro(),
+            "Run away! Summon an imaginary frog to defend yourself."
+        );
     }
 }
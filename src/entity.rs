@@ -1,46 +1,75 @@
-use crate::animation::{BlobAnims, BlobCurrentAnim, FireAnims, FrogAnims, JeanAnims};
-use crate::component::{Animation, CoordinateSystem, Follow, Position, Sprite, Velocity};
-use crate::image::{load_image, Image};
+use crate::animation::Animator;
+use crate::component::{
+    Animation, Blob, CoordinateSystem, Emitter, Fire, Follow, Frog, Jean, Light, Position,
+    PrevPosition, Sprite, Velocity,
+};
+use crate::image::{Atlas, Blend};
+use crate::light::PointLight;
 use randomize::PCG32;
 use std::collections::HashMap;
 use tiled::PropertyValue;
-use ultraviolet::{Vec2, Vec3};
+use ultraviolet::Vec3;
 
-type BlobStorage = (Position, Velocity, Sprite, Animation<BlobAnims>);
+type BlobStorage = (Blob, Position, PrevPosition, Velocity, Sprite, Animation);
+type JeanStorage = (
+    Jean,
+    Position,
+    PrevPosition,
+    Velocity,
+    Sprite,
+    Animation,
+    Light,
+);
+type FrogStorage = (
+    Frog,
+    Position,
+    PrevPosition,
+    Velocity,
+    Sprite,
+    Animation,
+    Follow,
+    Light,
+);
+type FireStorage = (Fire, Position, Sprite, Animation, Light);
+type EmitterStorage = (Position, Emitter);
 
-pub(crate) fn jean(pos: Vec3) -> (Position, Velocity, Sprite, Animation<JeanAnims>) {
-    let (width, height, image) = load_image(include_bytes!("../assets/jean.png"));
+pub(crate) fn jean(pos: Vec3, properties: &HashMap<String, PropertyValue>) -> JeanStorage {
+    let atlas = Atlas::new(
+        include_bytes!("../assets/jean.png"),
+        include_str!("../assets/jean.json"),
+    );
 
-    let image = Image::new(image, Vec2::new(width as f32, height as f32));
+    let prev_pos = PrevPosition(pos);
     let pos = Position(pos, CoordinateSystem::World);
     let vel = Velocity::default();
-    let sprite = Sprite {
-        image,
-        frame_height: 32,
-        frame_index: 0,
-    };
-    let anim = Animation(JeanAnims::new());
+    let sprite = Sprite::new(atlas, Blend::new(properties.get("blend")));
+    let anim = Animation(Animator::new(
+        &sprite.atlas,
+        include_str!("../assets/jean.anim.json"),
+    ));
+    let light =
+        Light(PointLight::new(32.0, [0xb4, 0xbe, 0xff], 0.0, 16.0).with_properties(properties));
 
-    (pos, vel, sprite, anim)
+    (Jean, pos, prev_pos, vel, sprite, anim, light)
 }
 
-pub(crate) fn frog(
-    pos: Vec3,
-    follow: Follow,
-) -> (Position, Velocity, Sprite, Animation<FrogAnims>, Follow) {
-    let (width, height, image) = load_image(include_bytes!("../assets/frog.png"));
+pub(crate) fn frog(pos: Vec3, follow: Follow) -> FrogStorage {
+    let atlas = Atlas::new(
+        include_bytes!("../assets/frog.png"),
+        include_str!("../assets/frog.json"),
+    );
 
-    let image = Image::new(image, Vec2::new(width as f32, height as f32));
+    let prev_pos = PrevPosition(pos);
     let pos = Position(pos, CoordinateSystem::World);
     let vel = Velocity::default();
-    let sprite = Sprite {
-        image,
-        frame_height: 19,
-        frame_index: 0,
-    };
-    let anim = Animation(FrogAnims::new());
+    let sprite = Sprite::new(atlas, Blend::Alpha);
+    let anim = Animation(Animator::new(
+        &sprite.atlas,
+        include_str!("../assets/frog.anim.json"),
+    ));
+    let light = Light(PointLight::new(20.0, [0x78, 0xff, 0x8c], 0.05, 8.0));
 
-    (pos, vel, sprite, anim, follow)
+    (Frog, pos, prev_pos, vel, sprite, anim, follow, light)
 }
 
 pub(crate) fn blob(
@@ -48,35 +77,61 @@ pub(crate) fn blob(
     properties: &HashMap<String, PropertyValue>,
     random: &mut PCG32,
 ) -> BlobStorage {
-    let (width, height, image) = load_image(include_bytes!("../assets/blob.png"));
+    let atlas = Atlas::new(
+        include_bytes!("../assets/blob.png"),
+        include_str!("../assets/blob.json"),
+    );
 
-    let image = Image::new(image, Vec2::new(width as f32, height as f32));
+    let prev_pos = PrevPosition(pos);
     let pos = Position(pos, CoordinateSystem::World);
     let vel = Velocity::default();
-    let sprite = Sprite {
-        image,
-        frame_height: 25,
-        frame_index: 0,
+    let sprite = Sprite::new(atlas, Blend::new(properties.get("blend")));
+    let mut animator = Animator::new(&sprite.atlas, include_str!("../assets/blob.anim.json"));
+    let left = match properties.get("direction") {
+        Some(PropertyValue::StringValue(direction)) if direction == "left" => true,
+        Some(PropertyValue::StringValue(direction)) if direction == "right" => false,
+        _ => random.next_u32() & 1 == 0,
     };
-    let anim = Animation(BlobAnims::new(BlobCurrentAnim::new(
-        random,
-        properties.get("direction"),
-    )));
+    animator.face_left(left);
+    let anim = Animation(animator);
 
-    (pos, vel, sprite, anim)
+    (Blob, pos, prev_pos, vel, sprite, anim)
 }
 
-pub(crate) fn fire(pos: Vec3, random: &mut PCG32) -> (Position, Sprite, Animation<FireAnims>) {
-    let (width, height, image) = load_image(include_bytes!("../assets/fire.png"));
+pub(crate) fn fire(
+    pos: Vec3,
+    properties: &HashMap<String, PropertyValue>,
+    random: &mut PCG32,
+) -> FireStorage {
+    let atlas = Atlas::new(
+        include_bytes!("../assets/fire.png"),
+        include_str!("../assets/fire.json"),
+    );
 
-    let image = Image::new(image, Vec2::new(width as f32, height as f32));
     let pos = Position(pos, CoordinateSystem::World);
-    let sprite = Sprite {
-        image,
-        frame_height: 32,
-        frame_index: 0,
+    let sprite = Sprite::new(atlas, Blend::new(properties.get("blend")));
+    let mut animator = Animator::new(&sprite.atlas, include_str!("../assets/fire.anim.json"));
+    animator.randomize(random);
+    let anim = Animation(animator);
+    let light =
+        Light(PointLight::new(48.0, [0xff, 0xa0, 0x50], 0.15, 16.0).with_properties(properties));
+
+    (Fire, pos, sprite, anim, light)
+}
+
+pub(crate) fn emitter(pos: Vec3, properties: &HashMap<String, PropertyValue>) -> EmitterStorage {
+    let sound = match properties.get("sound") {
+        Some(PropertyValue::StringValue(sound)) => sound.clone(),
+        _ => panic!("Emitter needs a sound property"),
     };
-    let anim = Animation(FireAnims::new(random));
+    let volume = match properties.get("volume") {
+        Some(PropertyValue::FloatValue(volume)) => *volume,
+        Some(PropertyValue::IntValue(volume)) => *volume as f32,
+        _ => 1.0,
+    };
+
+    let pos = Position(pos, CoordinateSystem::World);
+    let emitter = Emitter { sound, volume };
 
-    (pos, sprite, anim)
+    (pos, emitter)
 }
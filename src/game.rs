@@ -0,0 +1,187 @@
+use crate::capture;
+use crate::clock::GameClock;
+use crate::component::{self, Audio, Capture, Controls, Frame, Random};
+use crate::control;
+use crate::options::Options;
+use crate::settings::{Settings, Volume};
+use crate::system;
+use crate::world::load_world;
+use crate::{HEIGHT, WIDTH};
+use anyhow::Result;
+use log::error;
+use pixels::{Pixels, SurfaceTexture};
+use shipyard::{AllStoragesViewMut, NonSync, UniqueView, UniqueViewMut, World};
+use winit::dpi::LogicalSize;
+use winit::event::{Event, VirtualKeyCode, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::WindowBuilder;
+use winit_input_helper::WinitInputHelper;
+
+/// Run the game in a window.
+pub fn run(options: Options) -> Result<()> {
+    let (replay, seed) = options.replay_and_seed()?;
+    let mut settings = Settings::load();
+
+    let event_loop = EventLoop::new();
+    let mut input = WinitInputHelper::new();
+    let window = {
+        let min_size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
+        let size = LogicalSize::new(WIDTH as f64 * 6.0, HEIGHT as f64 * 6.0);
+        WindowBuilder::new()
+            .with_title("Sombervale")
+            .with_inner_size(size)
+            .with_min_inner_size(min_size)
+            .build(&event_loop)
+            .unwrap()
+    };
+
+    let mut pixels = {
+        let window_size = window.inner_size();
+        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
+        Pixels::new(WIDTH, HEIGHT, surface_texture)?
+    };
+
+    // Populate the world
+    let world = World::default();
+    let storages = world.borrow::<AllStoragesViewMut>().unwrap();
+    storages.add_unique_non_sync(Audio::new(&settings.volume));
+    storages.add_unique(Capture(capture::Capture::from_env()));
+    // Added before the rest of the world, which keeps these controls on every reload
+    storages.add_unique(Controls(control::Controls::new(settings.controls.clone())));
+    if let Some(replay) = replay {
+        storages.add_unique(component::Replay(replay));
+    }
+    load_world(storages, Random::from_seed(seed));
+
+    system::register_systems(&world);
+
+    event_loop.run(move |event, _, control_flow| {
+        // Draw the current frame
+        if let Event::RedrawRequested(_) = event {
+            world.run_workload("draw").expect("draw workload");
+
+            // Upload the rendered frame
+            let frame = world.borrow::<UniqueView<Frame>>().expect("get frame");
+            pixels.get_frame().copy_from_slice(&frame.0);
+
+            if pixels
+                .render()
+                .map_err(|e| error!("pixels.render() failed: {}", e))
+                .is_err()
+            {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+        }
+
+        // Handle controls
+        if let Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { input, .. },
+            ..
+        } = event
+        {
+            let mut controls = world
+                .borrow::<UniqueViewMut<Controls>>()
+                .expect("get pixels");
+
+            controls.0.update(input);
+        }
+
+        // Handle input events
+        if input.update(&event) {
+            // Close events
+            if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+
+            // Capture the most recently rendered frame
+            if input.key_pressed(VirtualKeyCode::F12) {
+                let frame = world.borrow::<UniqueView<Frame>>().expect("get frame");
+                let capture = world.borrow::<UniqueView<Capture>>().expect("get capture");
+                if let Err(err) = capture.0.screenshot(&frame.0) {
+                    error!("Screenshot failed: {}", err);
+                }
+            }
+
+            // Start or stop recording a GIF
+            if input.key_pressed(VirtualKeyCode::F11) {
+                let mut capture = world
+                    .borrow::<UniqueViewMut<Capture>>()
+                    .expect("get capture");
+                capture.0.toggle_recording();
+            }
+
+            // Adjust the volume, and remember it for next time
+            if update_volume(&input, &mut settings.volume) {
+                let mut audio = world
+                    .borrow::<NonSync<UniqueViewMut<Audio>>>()
+                    .expect("get audio");
+                audio.0.set_volume(&settings.volume);
+                if let Err(err) = settings.save() {
+                    error!("Saving settings failed: {}", err);
+                }
+            }
+
+            // Resize the window
+            if let Some(size) = input.window_resized() {
+                pixels.resize_surface(size.width, size.height);
+            }
+
+            // Simulate in fixed steps to catch up with wall-clock time, then request a redraw
+            world
+                .borrow::<UniqueViewMut<GameClock>>()
+                .expect("get clock")
+                .tick();
+            while world
+                .borrow::<UniqueViewMut<GameClock>>()
+                .expect("get clock")
+                .step()
+            {
+                world.run_workload("update").expect("update workload");
+            }
+
+            // Stop after the requested number of ticks
+            let clock = world.borrow::<UniqueView<GameClock>>().expect("get clock");
+            if options.ticks.is_some_and(|ticks| clock.ticks() >= ticks) {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            drop(clock);
+
+            window.request_redraw();
+        }
+    });
+}
+
+/// Handle the volume hotkeys. Returns `true` when the volume changed.
+///
+/// F1, F2 and F3 mute the master, music and sound effects volume. Each has a pair of keys to turn
+/// it down and up: `-` and `=`, `[` and `]`, `;` and `'`.
+fn update_volume(input: &WinitInputHelper, volume: &mut Volume) -> bool {
+    use VirtualKeyCode::*;
+
+    let buses = [
+        (&mut volume.master, F1, Minus, Equals),
+        (&mut volume.music, F2, LBracket, RBracket),
+        (&mut volume.sfx, F3, Semicolon, Apostrophe),
+    ];
+
+    let mut changed = false;
+    for (bus, mute, down, up) in buses {
+        if input.key_pressed(mute) {
+            bus.toggle_mute();
+            changed = true;
+        }
+        if input.key_pressed(down) {
+            bus.step(false);
+            changed = true;
+        }
+        if input.key_pressed(up) {
+            bus.step(true);
+            changed = true;
+        }
+    }
+
+    changed
+}
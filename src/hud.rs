@@ -1,11 +1,16 @@
-use crate::image::{self, bad_color_multiply, ImageViewMut};
+use crate::image::{self, Font, ImageViewMut};
 use crate::power::FrogPower;
 use ultraviolet::Vec2;
 
+// Horizontal padding around the message box
+const MESSAGE_MARGIN: f32 = 8.0;
+
 #[derive(Default)]
 pub(crate) struct Hud {
     pub(crate) jean: JeanStats,
     pub(crate) frog_power: Option<FrogPower>,
+    pub(crate) message: Option<String>,
+    pub(crate) font: Font,
 }
 
 pub(crate) struct JeanStats {
@@ -30,29 +35,51 @@ impl Default for JeanStats {
 }
 
 impl Hud {
-    pub(crate) fn draw(&self, dest: &mut ImageViewMut<'_>, factor: f32) {
-        let mut green = [0x38, 0xb7, 0x64, 0xff];
-        let mut purple = [0x5d, 0x27, 0x5d, 0xff];
-
-        bad_color_multiply(&mut green, factor);
-        bad_color_multiply(&mut purple, factor);
+    pub(crate) fn draw(&self, dest: &mut ImageViewMut<'_>) {
+        let green = [0x38, 0xb7, 0x64, 0xff];
+        let purple = [0x5d, 0x27, 0x5d, 0xff];
 
         // Draw HP meter
         let ratio = self.jean.hp as f32 / self.jean.max_hp as f32;
-        draw_meter(dest, Vec2::new(14.0, 3.0), green, ratio, factor);
+        draw_meter(dest, Vec2::new(14.0, 3.0), green, ratio);
 
         // Draw XP meter
         let ratio = self.jean.xp as f32 / self.jean.max_xp as f32;
-        draw_meter(dest, Vec2::new(40.0, 3.0), purple, ratio, factor);
+        draw_meter(dest, Vec2::new(40.0, 3.0), purple, ratio);
 
         if let Some(frog_power) = &self.frog_power {
             // Draw PP meter
             let ratio = frog_power.pp() as f32 / frog_power.max_pp() as f32;
-            draw_meter(dest, Vec2::new(14.0, 13.0), green, ratio, factor);
+            draw_meter(dest, Vec2::new(14.0, 13.0), green, ratio);
 
             // Draw XP meter
             let ratio = frog_power.xp() as f32 / frog_power.max_xp() as f32;
-            draw_meter(dest, Vec2::new(40.0, 13.0), purple, ratio, factor);
+            draw_meter(dest, Vec2::new(40.0, 13.0), purple, ratio);
+        }
+
+        if let Some(message) = &self.message {
+            self.draw_message(dest, message);
+        }
+    }
+
+    /// Draw a message centered along the bottom of the screen.
+    fn draw_message(&self, dest: &mut ImageViewMut<'_>, message: &str) {
+        let white = [0xf4, 0xf4, 0xf4, 0xff];
+        let shadow = [0x1a, 0x1c, 0x2c, 0xff];
+
+        let size = dest.size();
+        let lines = self.font.wrap(message, size.x - MESSAGE_MARGIN * 2.0);
+        let line_height = self.font.line_height();
+        let mut y = size.y - MESSAGE_MARGIN / 2.0 - lines.len() as f32 * line_height;
+
+        for line in &lines {
+            let x = ((size.x - self.font.measure(line).x) / 2.0).floor();
+            let pos = Vec2::new(x, y);
+
+            self.font.draw(dest, pos + Vec2::one(), line, shadow);
+            self.font.draw(dest, pos, line, white);
+
+            y += line_height;
         }
     }
 
@@ -67,12 +94,9 @@ impl Hud {
     }
 }
 
-fn draw_meter(dest: &mut ImageViewMut<'_>, mut pos: Vec2, color: [u8; 4], ratio: f32, factor: f32) {
-    let mut white = [0xf4, 0xf4, 0xf4, 0xff];
-    let mut gray = [0x94, 0xb0, 0xc2, 0xff];
-
-    bad_color_multiply(&mut white, factor);
-    bad_color_multiply(&mut gray, factor);
+fn draw_meter(dest: &mut ImageViewMut<'_>, mut pos: Vec2, color: [u8; 4], ratio: f32) {
+    let white = [0xf4, 0xf4, 0xf4, 0xff];
+    let gray = [0x94, 0xb0, 0xc2, 0xff];
 
     let size = Vec2::new(20.0, 2.0);
 
@@ -83,15 +107,15 @@ fn draw_meter(dest: &mut ImageViewMut<'_>, mut pos: Vec2, color: [u8; 4], ratio:
         (Vec2::new(1.0, 5.0), Vec2::new(22.0, 5.0)),
         (Vec2::new(0.0, 1.0), Vec2::new(0.0, 4.0)),
     ];
-    image::lines(dest, pos + Vec2::unit_y(), white, &lines, factor);
+    image::lines(dest, pos + Vec2::unit_y(), white, &lines);
 
     // Fill meter, active side
     let active_size = Vec2::new(size.x * ratio, size.y);
     pos += Vec2::new(2.0, 3.0);
-    image::rect(dest, pos, color, active_size, factor);
+    image::rect(dest, pos, color, active_size);
 
     // Fill meter, inactive side
     let inactive_size = Vec2::new(size.x - active_size.x, size.y);
     pos += Vec2::new(active_size.x, 0.0);
-    image::rect(dest, pos, gray, inactive_size, factor);
+    image::rect(dest, pos, gray, inactive_size);
 }
@@ -1,6 +1,16 @@
+use crate::color::Color;
+use bitflags::bitflags;
 use line_drawing::Bresenham;
+use tiled::PropertyValue;
 use ultraviolet::Vec2;
 
+pub(crate) use atlas::{Atlas, AtlasFrame};
+pub(crate) use font::Font;
+
+mod aseprite;
+mod atlas;
+mod font;
+
 pub(crate) struct Image {
     data: Vec<u8>,
     size: Vec2,
@@ -11,6 +21,29 @@ pub(crate) struct ImageViewMut<'data> {
     size: Vec2,
 }
 
+bitflags! {
+    /// Mirror the source image while blitting.
+    #[derive(Default)]
+    pub(crate) struct Flip: u8 {
+        const HORIZONTAL = 0b01;
+        const VERTICAL = 0b10;
+    }
+}
+
+/// How source pixels are combined with the destination when blitting.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) enum Blend {
+    /// Source-over alpha compositing.
+    #[default]
+    Alpha,
+
+    /// Source color is added to the destination, weighted by source alpha.
+    Add,
+
+    /// Destination color is multiplied by source color, weighted by source alpha.
+    Multiply,
+}
+
 impl Image {
     pub(crate) fn new(data: Vec<u8>, size: Vec2) -> Self {
         Self { data, size }
@@ -25,6 +58,10 @@ impl<'data> ImageViewMut<'data> {
     pub(crate) fn new(data: &'data mut [u8], size: Vec2) -> Self {
         Self { data, size }
     }
+
+    pub(crate) fn size(&self) -> Vec2 {
+        self.size
+    }
 }
 
 pub(crate) fn load_image(png: &[u8]) -> (isize, isize, Vec<u8>) {
@@ -36,14 +73,45 @@ pub(crate) fn load_image(png: &[u8]) -> (isize, isize, Vec<u8>) {
     (width, height, image)
 }
 
-pub(crate) fn bad_color_multiply(color: &mut [u8; 4], factor: f32) {
-    fn mult_u8(value: &mut u8, factor: f32) {
-        *value = (*value as f32 * factor) as u8;
+impl Blend {
+    /// Select a blend mode from an optional Tiled object property.
+    pub(crate) fn new(blend: Option<&PropertyValue>) -> Self {
+        blend
+            .and_then(|prop| match prop {
+                PropertyValue::StringValue(blend) => match blend.as_str() {
+                    "alpha" => Some(Self::Alpha),
+                    "add" => Some(Self::Add),
+                    "multiply" => Some(Self::Multiply),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .unwrap_or_default()
     }
 
-    mult_u8(&mut color[0], factor);
-    mult_u8(&mut color[1], factor);
-    mult_u8(&mut color[2], factor);
+    /// Combine a single `src` pixel into `dest`.
+    ///
+    /// Blending is done in linear light with premultiplied alpha.
+    fn apply(self, dest: &mut [u8], src: [u8; 4]) {
+        match src[3] {
+            0 => return,
+            0xff if self == Self::Alpha => {
+                dest.copy_from_slice(&src);
+                return;
+            }
+            _ => (),
+        }
+
+        let src = Color::from_srgba(src);
+        let dest_color = Color::from_srgba([dest[0], dest[1], dest[2], dest[3]]);
+        let color = match self {
+            Self::Alpha => src.over(dest_color),
+            Self::Add => src.add(dest_color),
+            Self::Multiply => src.multiply(dest_color),
+        };
+
+        dest.copy_from_slice(&color.to_srgba());
+    }
 }
 
 pub(crate) fn blit<'dest>(
@@ -52,23 +120,32 @@ pub(crate) fn blit<'dest>(
     src: &Image,
     mut src_pos: Vec2,
     mut size: Vec2,
-    factor: f32,
+    blend: Blend,
+    flip: Flip,
 ) {
     assert!(size.x <= src.size.x);
     assert!(size.y <= src.size.y);
     assert!(size.x <= dest.size.x);
     assert!(size.y <= dest.size.y);
 
+    let flip_h = flip.contains(Flip::HORIZONTAL);
+    let flip_v = flip.contains(Flip::VERTICAL);
+
     // Account for src_pos being negative
+    // When flipped, the clipped edge ends up on the opposite side of the destination
     if src_pos.x < 0.0 {
         let offset = src_pos.x.abs();
-        dest_pos.x += offset;
+        if !flip_h {
+            dest_pos.x += offset;
+        }
         size.x -= offset;
         src_pos.x = 0.0;
     }
     if src_pos.y < 0.0 {
         let offset = src_pos.y.abs();
-        dest_pos.y += offset;
+        if !flip_v {
+            dest_pos.y += offset;
+        }
         size.y -= offset;
         src_pos.y = 0.0;
     }
@@ -80,11 +157,25 @@ pub(crate) fn blit<'dest>(
 
     // Adjust the size to prevent wrapping
     if src_pos.x + size.x > src.size.x {
-        size.x -= src_pos.x + size.x - src.size.x;
+        let offset = src_pos.x + size.x - src.size.x;
+        if flip_h {
+            dest_pos.x += offset;
+        }
+        size.x -= offset;
         if size.x <= 0.0 {
             return;
         }
     }
+    if src_pos.y + size.y > src.size.y {
+        let offset = src_pos.y + size.y - src.size.y;
+        if flip_v {
+            dest_pos.y += offset;
+        }
+        size.y -= offset;
+        if size.y <= 0.0 {
+            return;
+        }
+    }
 
     // Bail early when the dest_pos is outside of the dest
     let lower_right = dest_pos + size;
@@ -96,48 +187,52 @@ pub(crate) fn blit<'dest>(
         return;
     }
 
-    let src_index = src_pos.y.mul_add(src.size.x, src_pos.x) as usize * 4;
-    let slice = &src.data[src_index..];
-    let rows = slice.chunks(src.size.x as usize * 4).take(size.y as usize);
+    let src_x = src_pos.x as usize;
+    let src_y = src_pos.y as usize;
+    let src_width = src.size.x as usize;
+    let width = size.x as usize;
+    let height = size.y as usize;
 
     let dest_x = dest_pos.x as isize;
     let dest_y = dest_pos.y as isize;
     let dest_width = dest.size.x as isize;
     let dest_height = dest.size.y as isize;
 
-    for (y, row) in rows.enumerate() {
-        for (x, color) in row.chunks_exact(4).take(size.x as usize).enumerate() {
-            if color[3] == 0xff {
-                let x = x as isize + dest_x;
-                let y = y as isize + dest_y;
+    for y in 0..height {
+        let row = if flip_v { height - 1 - y } else { y } + src_y;
+        let y = y as isize + dest_y;
 
-                // Early bail when drawing below destination image
-                if y >= dest_height as isize {
-                    return;
-                }
+        // Early bail when drawing below destination image
+        if y >= dest_height {
+            return;
+        }
+        if y < 0 {
+            continue;
+        }
+
+        for x in 0..width {
+            let col = if flip_h { width - 1 - x } else { x } + src_x;
+            let index = (row * src_width + col) * 4;
+            let color = [
+                src.data[index],
+                src.data[index + 1],
+                src.data[index + 2],
+                src.data[index + 3],
+            ];
 
-                if x >= 0 && x < dest_width && y >= 0 {
-                    let mut factored_color = [0; 4];
-                    factored_color.copy_from_slice(color);
-                    bad_color_multiply(&mut factored_color, factor);
+            if color[3] != 0 {
+                let x = x as isize + dest_x;
 
+                if x >= 0 && x < dest_width {
                     let index = ((y * dest_width + x) * 4) as usize;
-                    dest.data[index..index + 4].copy_from_slice(&factored_color);
+                    blend.apply(&mut dest.data[index..index + 4], color);
                 }
             }
         }
     }
 }
 
-pub(crate) fn rect(
-    dest: &mut ImageViewMut<'_>,
-    pos: Vec2,
-    mut color: [u8; 4],
-    size: Vec2,
-    factor: f32,
-) {
-    bad_color_multiply(&mut color, factor);
-
+pub(crate) fn rect(dest: &mut ImageViewMut<'_>, pos: Vec2, color: [u8; 4], size: Vec2) {
     let x = pos.x as usize;
     let y = pos.y as usize;
     let w = size.x as usize;
@@ -154,12 +249,9 @@ pub(crate) fn rect(
 pub(crate) fn lines(
     dest: &mut ImageViewMut<'_>,
     pos: Vec2,
-    mut color: [u8; 4],
+    color: [u8; 4],
     lines: &[(Vec2, Vec2)],
-    factor: f32,
 ) {
-    bad_color_multiply(&mut color, factor);
-
     for (start, end) in lines {
         let start = (start.x as isize, start.y as isize);
         let end = (end.x as isize, end.y as isize);
@@ -171,3 +263,91 @@ pub(crate) fn lines(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_apply() {
+        // Fully transparent source leaves the destination untouched
+        let mut dest = [0x10, 0x20, 0x30, 0xff];
+        Blend::Alpha.apply(&mut dest, [0xff, 0xff, 0xff, 0]);
+        assert_eq!(dest, [0x10, 0x20, 0x30, 0xff]);
+
+        // Opaque source replaces the destination
+        Blend::Alpha.apply(&mut dest, [0xff, 0x80, 0x00, 0xff]);
+        assert_eq!(dest, [0xff, 0x80, 0x00, 0xff]);
+
+        // Half transparent source over opaque destination mixes in linear light
+        let mut dest = [0x00, 0x00, 0xff, 0xff];
+        Blend::Alpha.apply(&mut dest, [0xff, 0x00, 0x00, 0x80]);
+        assert_eq!(dest, [0xbc, 0x00, 0xbb, 0xff]);
+
+        // Half transparent source over transparent destination keeps its own color
+        let mut dest = [0x00, 0x00, 0x00, 0x00];
+        Blend::Alpha.apply(&mut dest, [0xff, 0x00, 0x00, 0x80]);
+        assert_eq!(dest, [0xff, 0x00, 0x00, 0x80]);
+
+        // Additive saturates
+        let mut dest = [0xf0, 0x10, 0x00, 0xff];
+        Blend::Add.apply(&mut dest, [0x80, 0x20, 0x00, 0xff]);
+        assert_eq!(dest, [0xff, 0x26, 0x00, 0xff]);
+
+        // Multiply darkens
+        let mut dest = [0xff, 0x80, 0x40, 0xff];
+        Blend::Multiply.apply(&mut dest, [0x80, 0x80, 0xff, 0xff]);
+        assert_eq!(dest, [0x80, 0x3d, 0x40, 0xff]);
+    }
+
+    #[test]
+    fn test_blit_flip() {
+        // 2x2 source with distinct opaque pixels
+        #[rustfmt::skip]
+        let src = Image::new(
+            vec![
+                1, 0, 0, 0xff, 2, 0, 0, 0xff,
+                3, 0, 0, 0xff, 4, 0, 0, 0xff,
+            ],
+            Vec2::new(2.0, 2.0),
+        );
+        let red = |data: &[u8]| data.chunks_exact(4).map(|c| c[0]).collect::<Vec<_>>();
+
+        let cases = [
+            (Flip::empty(), [1, 2, 3, 4]),
+            (Flip::HORIZONTAL, [2, 1, 4, 3]),
+            (Flip::VERTICAL, [3, 4, 1, 2]),
+            (Flip::HORIZONTAL | Flip::VERTICAL, [4, 3, 2, 1]),
+        ];
+        for (flip, expected) in cases {
+            let mut data = vec![0; 16];
+            let mut dest = ImageViewMut::new(&mut data, Vec2::new(2.0, 2.0));
+            let size = Vec2::new(2.0, 2.0);
+            blit(
+                &mut dest,
+                Vec2::zero(),
+                &src,
+                Vec2::zero(),
+                size,
+                Blend::Alpha,
+                flip,
+            );
+            assert_eq!(red(&data), expected, "{:?}", flip);
+        }
+
+        // Clipping the left edge of the source removes the right edge of a mirrored blit
+        let mut data = vec![0; 16];
+        let mut dest = ImageViewMut::new(&mut data, Vec2::new(2.0, 2.0));
+        let (pos, size) = (Vec2::new(-1.0, 0.0), Vec2::new(2.0, 2.0));
+        blit(
+            &mut dest,
+            Vec2::zero(),
+            &src,
+            pos,
+            size,
+            Blend::Alpha,
+            Flip::HORIZONTAL,
+        );
+        assert_eq!(red(&data), [1, 0, 3, 0]);
+    }
+}
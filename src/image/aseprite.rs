@@ -0,0 +1,264 @@
+//! Import sprite sheets exported by Aseprite.
+//!
+//! Sheets must be exported with the "Array" JSON layout, frame tags and slices enabled. Each
+//! frame tag becomes an animation clip. A slice named `pivot` sets the frame pivot; otherwise the
+//! pivot is the upper left corner. Frames can be found by their `filename`, which is set by the
+//! "Item Filename" export option, e.g. `{tag}` or `{title} {frame}`.
+
+use super::AtlasFrame;
+use crate::animation::{Clip, Frame};
+use anyhow::{ensure, Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use ultraviolet::Vec2;
+
+pub(super) struct SpriteSheet {
+    pub(super) frames: Vec<AtlasFrame>,
+    pub(super) clips: HashMap<String, Clip>,
+    pub(super) names: HashMap<String, usize>,
+}
+
+#[derive(Deserialize)]
+struct Document {
+    frames: Vec<SheetFrame>,
+    meta: Meta,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SheetFrame {
+    filename: String,
+    frame: Rect,
+    sprite_source_size: Rect,
+    duration: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Meta {
+    #[serde(default)]
+    frame_tags: Vec<FrameTag>,
+    #[serde(default)]
+    slices: Vec<Slice>,
+}
+
+#[derive(Deserialize)]
+struct FrameTag {
+    name: String,
+    from: usize,
+    to: usize,
+    direction: Direction,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+#[derive(Deserialize)]
+struct Slice {
+    name: String,
+    keys: Vec<SliceKey>,
+}
+
+#[derive(Deserialize)]
+struct SliceKey {
+    frame: usize,
+    bounds: Rect,
+    pivot: Option<Point>,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+struct Rect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+pub(super) fn parse(json: &str) -> Result<SpriteSheet> {
+    let doc: Document = serde_json::from_str(json)
+        .context("Expected an Aseprite sprite sheet exported with the Array layout")?;
+    ensure!(!doc.frames.is_empty(), "Sprite sheet has no frames");
+
+    let pivots = doc.meta.slices.iter().find(|slice| slice.name == "pivot");
+    let frames = doc
+        .frames
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            // Pivots are relative to the untrimmed canvas
+            let pivot = pivots.and_then(|slice| pivot(slice, index));
+            let offset = Vec2::new(frame.sprite_source_size.x, frame.sprite_source_size.y);
+
+            AtlasFrame {
+                pos: Vec2::new(frame.frame.x, frame.frame.y),
+                size: Vec2::new(frame.frame.w, frame.frame.h),
+                pivot: pivot.unwrap_or_default() - offset,
+            }
+        })
+        .collect();
+
+    // The first frame wins when several share a name
+    let mut names = HashMap::new();
+    for (index, frame) in doc.frames.iter().enumerate() {
+        names.entry(frame.filename.clone()).or_insert(index);
+    }
+
+    let mut clips = HashMap::new();
+    for tag in &doc.meta.frame_tags {
+        ensure!(
+            tag.from <= tag.to && tag.to < doc.frames.len(),
+            "Frame tag {} is out of range",
+            tag.name
+        );
+
+        let indices = clip_indices(tag.from, tag.to, tag.direction);
+        let frames = indices
+            .map(|index| Frame {
+                index,
+                duration: Duration::from_millis(doc.frames[index].duration),
+            })
+            .collect();
+        clips.insert(tag.name.clone(), Clip(frames));
+    }
+
+    Ok(SpriteSheet {
+        frames,
+        clips,
+        names,
+    })
+}
+
+/// The pivot of the slice key that applies to the frame, if any.
+fn pivot(slice: &Slice, index: usize) -> Option<Vec2> {
+    // Keys apply from their frame until the next key
+    let key = slice.keys.iter().rev().find(|key| key.frame <= index)?;
+    let pivot = key.pivot?;
+
+    Some(Vec2::new(key.bounds.x + pivot.x, key.bounds.y + pivot.y))
+}
+
+/// Frame indices for one cycle of a tag.
+fn clip_indices(from: usize, to: usize, direction: Direction) -> Box<dyn Iterator<Item = usize>> {
+    // Ping-pong cycles do not repeat the end frames when looping
+    let inner = from + 1..to;
+
+    match direction {
+        Direction::Forward => Box::new(from..=to),
+        Direction::Reverse => Box::new((from..=to).rev()),
+        Direction::Pingpong => Box::new((from..=to).chain(inner.rev())),
+        Direction::PingpongReverse => Box::new((from..=to).rev().chain(inner)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = r#"{
+        "frames": [
+            {
+                "filename": "test 0.aseprite",
+                "frame": { "x": 0, "y": 0, "w": 8, "h": 8 },
+                "rotated": false,
+                "trimmed": false,
+                "spriteSourceSize": { "x": 0, "y": 0, "w": 8, "h": 8 },
+                "sourceSize": { "w": 8, "h": 8 },
+                "duration": 100
+            },
+            {
+                "filename": "test 1.aseprite",
+                "frame": { "x": 8, "y": 0, "w": 6, "h": 7 },
+                "rotated": false,
+                "trimmed": true,
+                "spriteSourceSize": { "x": 1, "y": 1, "w": 6, "h": 7 },
+                "sourceSize": { "w": 8, "h": 8 },
+                "duration": 200
+            },
+            {
+                "filename": "test 2.aseprite",
+                "frame": { "x": 0, "y": 8, "w": 8, "h": 8 },
+                "rotated": false,
+                "trimmed": false,
+                "spriteSourceSize": { "x": 0, "y": 0, "w": 8, "h": 8 },
+                "sourceSize": { "w": 8, "h": 8 },
+                "duration": 300
+            }
+        ],
+        "meta": {
+            "frameTags": [
+                { "name": "loop", "from": 0, "to": 2, "direction": "pingpong" },
+                { "name": "back", "from": 1, "to": 2, "direction": "reverse" }
+            ],
+            "slices": [
+                {
+                    "name": "pivot",
+                    "keys": [
+                        {
+                            "frame": 0,
+                            "bounds": { "x": 0, "y": 0, "w": 8, "h": 8 },
+                            "pivot": { "x": 4, "y": 8 }
+                        },
+                        {
+                            "frame": 2,
+                            "bounds": { "x": 2, "y": 0, "w": 4, "h": 8 },
+                            "pivot": { "x": 1, "y": 8 }
+                        }
+                    ]
+                }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_sprite_sheet() {
+        let sheet = parse(SHEET).unwrap();
+
+        // Trimmed frames keep the pivot in place on the canvas
+        let pivots = sheet.frames.iter().map(|frame| frame.pivot);
+        let expected = [
+            Vec2::new(4.0, 8.0),
+            Vec2::new(3.0, 7.0),
+            Vec2::new(3.0, 8.0),
+        ];
+        assert!(pivots.eq(expected));
+        assert_eq!(sheet.frames[1].pos, Vec2::new(8.0, 0.0));
+        assert_eq!(sheet.frames[1].size, Vec2::new(6.0, 7.0));
+
+        let indices = |name: &str| {
+            sheet.clips[name]
+                .0
+                .iter()
+                .map(|frame| frame.index)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(indices("loop"), [0, 1, 2, 1]);
+        assert_eq!(indices("back"), [2, 1]);
+        assert_eq!(
+            sheet.clips["back"].0[0].duration,
+            Duration::from_millis(300)
+        );
+
+        assert_eq!(sheet.names["test 1.aseprite"], 1);
+        assert_eq!(sheet.names.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_rejects_hash_layout() {
+        let json = r#"{ "frames": { "test 0.aseprite": {} }, "meta": {} }"#;
+
+        assert!(parse(json).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use super::{aseprite, load_image, Image};
+use crate::animation::Clip;
+use std::collections::HashMap;
+use ultraviolet::Vec2;
+
+/// A sprite sheet: an image with the rectangle, pivot and name of every frame, and named animation
+/// clips.
+///
+/// Frames may be packed anywhere in the image. The pivot is the point within the frame that is
+/// placed on the entity position.
+pub(crate) struct Atlas {
+    image: Image,
+    frames: Vec<AtlasFrame>,
+    clips: HashMap<String, Clip>,
+    names: HashMap<String, usize>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct AtlasFrame {
+    pub(crate) pos: Vec2,
+    pub(crate) size: Vec2,
+    pub(crate) pivot: Vec2,
+}
+
+impl Atlas {
+    /// Load a sprite sheet exported by Aseprite.
+    pub(crate) fn new(png: &[u8], json: &str) -> Self {
+        let (width, height, image) = load_image(png);
+        let image = Image::new(image, Vec2::new(width as f32, height as f32));
+        let sheet = aseprite::parse(json).expect("Invalid sprite sheet");
+
+        for (index, frame) in sheet.frames.iter().enumerate() {
+            let max = frame.pos + frame.size;
+            assert!(
+                max.x <= image.size().x && max.y <= image.size().y,
+                "Atlas frame {} is outside of the image",
+                index
+            );
+        }
+
+        Self {
+            image,
+            frames: sheet.frames,
+            clips: sheet.clips,
+            names: sheet.names,
+        }
+    }
+
+    pub(crate) fn image(&self) -> &Image {
+        &self.image
+    }
+
+    pub(crate) fn frame(&self, index: usize) -> &AtlasFrame {
+        &self.frames[index]
+    }
+
+    /// Find the index of a frame by name.
+    pub(crate) fn find(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    pub(crate) fn clip(&self, name: &str) -> &Clip {
+        self.clips
+            .get(name)
+            .unwrap_or_else(|| panic!("Atlas has no {} clip", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atlas_frames() {
+        let atlas = Atlas::new(
+            include_bytes!("../../assets/jean.png"),
+            include_str!("../../assets/jean.json"),
+        );
+
+        // Frames wrap onto multiple rows
+        let walk = atlas.clip("walk");
+        let frame = atlas.frame(walk.0[2].index);
+        assert_eq!(frame.pos, Vec2::new(0.0, 32.0));
+        assert_eq!(frame.size, Vec2::new(20.0, 32.0));
+        assert_eq!(frame.pivot, Vec2::new(10.0, 32.0));
+
+        assert_eq!(atlas.find("jean 0.aseprite"), Some(0));
+        assert_eq!(atlas.find("missing"), None);
+    }
+
+    #[test]
+    fn test_atlas_find() {
+        let atlas = Atlas::new(
+            include_bytes!("../../assets/hud.png"),
+            include_str!("../../assets/hud.json"),
+        );
+
+        assert_eq!(atlas.find("jean"), Some(0));
+        assert_eq!(atlas.find("frog"), Some(1));
+    }
+}
@@ -0,0 +1,234 @@
+use super::{load_image, Blend, Image, ImageViewMut};
+use std::collections::HashMap;
+use ultraviolet::Vec2;
+
+/// A bitmap font: a glyph atlas with an advance and kerning table.
+///
+/// The table is parsed from the text variant of the AngelCode BMFont format.
+/// Glyphs are drawn as a mask, so the atlas should be white on a transparent background.
+pub(crate) struct Font {
+    image: Image,
+    line_height: f32,
+    glyphs: HashMap<char, Glyph>,
+    kerning: HashMap<(char, char), f32>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Glyph {
+    pos: Vec2,
+    size: Vec2,
+    offset: Vec2,
+    advance: f32,
+}
+
+impl Font {
+    pub(crate) fn new(png: &[u8], fnt: &str) -> Self {
+        let (width, height, image) = load_image(png);
+        let image = Image::new(image, Vec2::new(width as f32, height as f32));
+
+        let mut line_height = 0.0;
+        let mut glyphs = HashMap::new();
+        let mut kerning = HashMap::new();
+
+        for line in fnt.lines() {
+            let record = Record::parse(line);
+            let chr = |key: &str| -> char {
+                std::char::from_u32(record.num(key) as u32).expect("Invalid font character")
+            };
+
+            match record.tag {
+                Some("common") => {
+                    line_height = record.num("lineHeight");
+                }
+                Some("char") => {
+                    let glyph = Glyph {
+                        pos: Vec2::new(record.num("x"), record.num("y")),
+                        size: Vec2::new(record.num("width"), record.num("height")),
+                        offset: Vec2::new(record.num("xoffset"), record.num("yoffset")),
+                        advance: record.num("xadvance"),
+                    };
+                    glyphs.insert(chr("id"), glyph);
+                }
+                Some("kerning") => {
+                    kerning.insert((chr("first"), chr("second")), record.num("amount"));
+                }
+                // Ignore everything else
+                _ => {}
+            }
+        }
+
+        Self {
+            image,
+            line_height,
+            glyphs,
+            kerning,
+        }
+    }
+
+    pub(crate) fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    fn glyph(&self, chr: char) -> Option<&Glyph> {
+        self.glyphs.get(&chr).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    fn kerning(&self, prev: Option<char>, chr: char) -> f32 {
+        prev.and_then(|prev| self.kerning.get(&(prev, chr)))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Measure the size of a single line of text.
+    pub(crate) fn measure(&self, text: &str) -> Vec2 {
+        let mut width = 0.0;
+        let mut prev = None;
+
+        for chr in text.chars() {
+            if let Some(glyph) = self.glyph(chr) {
+                width += self.kerning(prev, chr) + glyph.advance;
+            }
+            prev = Some(chr);
+        }
+
+        Vec2::new(width, self.line_height)
+    }
+
+    /// Split text into lines that fit within `max_width`.
+    ///
+    /// Lines are broken at whitespace and at explicit newlines. Words that are too long to fit
+    /// on a line by themselves are not broken.
+    pub(crate) fn wrap(&self, text: &str, max_width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for paragraph in text.lines() {
+            let mut line = String::new();
+
+            for word in paragraph.split_whitespace() {
+                if line.is_empty() {
+                    line.push_str(word);
+                    continue;
+                }
+
+                let candidate = format!("{} {}", line, word);
+                if self.measure(&candidate).x > max_width {
+                    lines.push(line);
+                    line = word.to_string();
+                } else {
+                    line = candidate;
+                }
+            }
+
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    /// Draw a single line of text with its upper left corner at `pos`.
+    pub(crate) fn draw(&self, dest: &mut ImageViewMut<'_>, pos: Vec2, text: &str, color: [u8; 4]) {
+        let mut cursor = pos;
+        let mut prev = None;
+
+        for chr in text.chars() {
+            if let Some(glyph) = self.glyph(chr) {
+                cursor.x += self.kerning(prev, chr);
+                self.draw_glyph(dest, cursor + glyph.offset, glyph, color);
+                cursor.x += glyph.advance;
+            }
+            prev = Some(chr);
+        }
+    }
+
+    fn draw_glyph(&self, dest: &mut ImageViewMut<'_>, pos: Vec2, glyph: &Glyph, color: [u8; 4]) {
+        let src_width = self.image.size.x as usize;
+        let dest_x = pos.x as isize;
+        let dest_y = pos.y as isize;
+        let dest_width = dest.size.x as isize;
+        let dest_height = dest.size.y as isize;
+
+        for y in 0..glyph.size.y as usize {
+            let row = glyph.pos.y as usize + y;
+            let y = y as isize + dest_y;
+            if y < 0 || y >= dest_height {
+                continue;
+            }
+
+            for x in 0..glyph.size.x as usize {
+                let col = glyph.pos.x as usize + x;
+                let x = x as isize + dest_x;
+                if x < 0 || x >= dest_width {
+                    continue;
+                }
+
+                // The atlas alpha channel is used as a coverage mask for the text color
+                let coverage = self.image.data[(row * src_width + col) * 4 + 3] as u32;
+                let alpha = (color[3] as u32 * coverage / 0xff) as u8;
+                let index = ((y * dest_width + x) * 4) as usize;
+                let color = [color[0], color[1], color[2], alpha];
+                Blend::Alpha.apply(&mut dest.data[index..index + 4], color);
+            }
+        }
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self::new(
+            include_bytes!("../../assets/font.png"),
+            include_str!("../../assets/font.fnt"),
+        )
+    }
+}
+
+/// One line of a `tag key=value ...` text file, as used by BMFont.
+struct Record<'a> {
+    line: &'a str,
+    tag: Option<&'a str>,
+    attrs: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Record<'a> {
+    fn parse(line: &'a str) -> Self {
+        let mut tokens = line.split_whitespace();
+        let tag = tokens.next();
+        let attrs = tokens.filter_map(|token| token.split_once('=')).collect();
+
+        Self { line, tag, attrs }
+    }
+
+    fn str(&self, key: &str) -> &'a str {
+        self.attrs
+            .get(key)
+            .unwrap_or_else(|| panic!("Attribute {} is missing in {:?}", key, self.line))
+    }
+
+    fn num(&self, key: &str) -> f32 {
+        self.str(key)
+            .parse()
+            .unwrap_or_else(|_| panic!("Attribute {} is not a number in {:?}", key, self.line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_measure_and_wrap() {
+        let font = Font::default();
+
+        // "A" is 3 pixels wide with 1 pixel of spacing
+        assert_eq!(font.measure("A"), Vec2::new(4.0, 7.0));
+        assert_eq!(font.measure("AA"), Vec2::new(8.0, 7.0));
+
+        // Kerning pulls "o" under the arm of "T"
+        assert_eq!(
+            font.measure("To").x,
+            font.measure("T").x + font.measure("o").x - 1.0
+        );
+
+        let lines = font.wrap("A AA AAA\nAAAA", 16.0);
+        assert_eq!(lines, ["A AA", "AAA", "AAAA"]);
+    }
+}
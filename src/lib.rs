@@ -0,0 +1,30 @@
+#![deny(clippy::all)]
+#![forbid(unsafe_code)]
+
+pub use crate::game::run;
+pub use crate::options::Options;
+pub use crate::simulation::{Simulation, Summary};
+
+mod animation;
+mod audio;
+mod capture;
+mod clock;
+mod color;
+mod component;
+mod control;
+mod entity;
+mod game;
+mod hud;
+mod image;
+mod light;
+mod map;
+mod options;
+mod power;
+mod replay;
+mod settings;
+mod simulation;
+mod system;
+mod world;
+
+pub(crate) const WIDTH: u32 = 160;
+pub(crate) const HEIGHT: u32 = 128;
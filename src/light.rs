@@ -0,0 +1,191 @@
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use randomize::PCG32;
+use std::collections::HashMap;
+use std::time::Duration;
+use tiled::PropertyValue;
+use ultraviolet::{Vec2, Vec3};
+
+// Average time for the flicker to reach a new intensity
+const FLICKER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A radial light attached to an entity.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct PointLight {
+    /// Distance in pixels where the light fades out completely.
+    radius: f32,
+
+    /// Light color in linear light, each channel in the range `0.0..=1.0`.
+    color: Vec3,
+
+    /// Maximum fraction of the radius lost to flickering.
+    flicker: f32,
+
+    /// Height of the light source above the entity position, in pixels.
+    height: f32,
+
+    /// Current flicker state, updated every tick.
+    intensity: f32,
+
+    /// Intensity the flicker is easing toward, and the time left to reach it.
+    target: f32,
+    remaining: Duration,
+}
+
+/// Per-pixel light levels for one frame.
+pub(crate) struct LightMap {
+    data: Vec<Vec3>,
+    size: Vec2,
+}
+
+impl PointLight {
+    pub(crate) fn new(radius: f32, color: [u8; 3], flicker: f32, height: f32) -> Self {
+        Self {
+            radius,
+            color: decode_color(color),
+            flicker,
+            height,
+            intensity: 1.0,
+            target: 1.0,
+            remaining: Duration::ZERO,
+        }
+    }
+
+    /// Override the defaults with `light_radius`, `light_color` and `light_flicker` properties.
+    pub(crate) fn with_properties(mut self, properties: &HashMap<String, PropertyValue>) -> Self {
+        self.radius = match properties.get("light_radius") {
+            Some(PropertyValue::FloatValue(radius)) => *radius,
+            Some(PropertyValue::IntValue(radius)) => *radius as f32,
+            _ => self.radius,
+        };
+        if let Some(PropertyValue::ColorValue(argb)) = properties.get("light_color") {
+            let [_, r, g, b] = argb.to_be_bytes();
+            self.color = decode_color([r, g, b]);
+        }
+        let flicker = match properties.get("light_flicker") {
+            Some(PropertyValue::FloatValue(flicker)) => *flicker,
+            Some(PropertyValue::IntValue(flicker)) => *flicker as f32,
+            _ => self.flicker,
+        };
+        self.flicker = flicker.clamp(0.0, 1.0);
+
+        self
+    }
+
+    pub(crate) fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// Ease the intensity toward a random target by `dt`, picking a new target when it is reached.
+    pub(crate) fn flicker(&mut self, dt: Duration, random: &mut PCG32) {
+        if self.remaining.is_zero() {
+            let mut next = || randomize::f32_half_open_right(random.next_u32());
+            self.target = 1.0 - self.flicker * next();
+            self.remaining = FLICKER_INTERVAL.mul_f32(0.5 + next());
+        }
+
+        let t = (dt.as_secs_f32() / self.remaining.as_secs_f32()).min(1.0);
+        self.intensity += (self.target - self.intensity) * t;
+        self.remaining = self.remaining.saturating_sub(dt);
+    }
+
+    fn radius(&self) -> f32 {
+        self.radius * self.intensity
+    }
+}
+
+impl LightMap {
+    /// Create a light map where every pixel is lit by the `ambient` level.
+    pub(crate) fn new(size: Vec2, ambient: f32) -> Self {
+        let len = (size.x * size.y) as usize;
+
+        Self {
+            data: vec![Vec3::broadcast(ambient); len],
+            size,
+        }
+    }
+
+    /// Add a light centered on the screen position `center`.
+    pub(crate) fn add(&mut self, center: Vec2, light: &PointLight) {
+        let radius = light.radius();
+        if radius <= 0.0 {
+            return;
+        }
+
+        // Only visit pixels inside the light's bounding box
+        let min = (center - Vec2::broadcast(radius)).max_by_component(Vec2::zero());
+        let max = (center + Vec2::broadcast(radius)).min_by_component(self.size);
+        let width = self.size.x as usize;
+
+        for y in min.y as usize..max.y.ceil().max(0.0) as usize {
+            for x in min.x as usize..max.x.ceil().max(0.0) as usize {
+                let dist = (Vec2::new(x as f32, y as f32) - center).mag();
+                if dist < radius {
+                    let falloff = 1.0 - dist / radius;
+                    self.data[y * width + x] += light.color * falloff * falloff;
+                }
+            }
+        }
+    }
+
+    /// Darken the frame by the light level of each pixel, in linear light.
+    pub(crate) fn apply(&self, frame: &mut [u8]) {
+        for (pixel, level) in frame.chunks_exact_mut(4).zip(self.data.iter()) {
+            let level = level.min_by_component(Vec3::one());
+
+            pixel[0] = linear_to_srgb(srgb_to_linear(pixel[0]) * level.x);
+            pixel[1] = linear_to_srgb(srgb_to_linear(pixel[1]) * level.y);
+            pixel[2] = linear_to_srgb(srgb_to_linear(pixel[2]) * level.z);
+        }
+    }
+}
+
+fn decode_color(color: [u8; 3]) -> Vec3 {
+    Vec3::new(
+        srgb_to_linear(color[0]),
+        srgb_to_linear(color[1]),
+        srgb_to_linear(color[2]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_properties() {
+        let default = PointLight::new(32.0, [0xff; 3], 0.0, 0.0);
+
+        // Tiled writes whole numbers as integers
+        let properties = vec![
+            ("light_radius".to_string(), PropertyValue::IntValue(48)),
+            ("light_flicker".to_string(), PropertyValue::IntValue(2)),
+        ];
+        let light = default.with_properties(&properties.into_iter().collect());
+        assert_eq!(light.radius, 48.0);
+        assert_eq!(light.flicker, 1.0);
+
+        let properties = vec![("light_radius".to_string(), PropertyValue::FloatValue(12.5))];
+        let light = default.with_properties(&properties.into_iter().collect());
+        assert_eq!(light.radius, 12.5);
+        assert_eq!(light.flicker, 0.0);
+
+        let light = default.with_properties(&HashMap::new());
+        assert_eq!(light.radius, 32.0);
+    }
+
+    #[test]
+    fn test_flicker() {
+        let mut light = PointLight::new(48.0, [0xff; 3], 0.5, 0.0);
+        let mut random = PCG32::seed(1, 1);
+        let dt = Duration::from_nanos(16_666_667);
+
+        // Each tick moves a fraction of the way, so the light never jumps between extremes
+        let mut prev = light.intensity;
+        for _ in 0..600 {
+            light.flicker(dt, &mut random);
+            assert!((0.5..=1.0).contains(&light.intensity));
+            assert!((light.intensity - prev).abs() <= 0.5 * dt.as_secs_f32() / 0.05);
+            prev = light.intensity;
+        }
+    }
+}
@@ -1,7 +1,9 @@
-use crate::component::{Collision, CoordinateSystem, Position, Random, Sprite, Tilemap, Viewport};
+use crate::component::{
+    Ambient, Collision, Controls, CoordinateSystem, Position, Random, Sprite, Tilemap, Viewport,
+};
 use crate::entity;
 use crate::hud::Hud;
-use crate::image::{blit, load_image, Image, ImageViewMut};
+use crate::image::{blit, load_image, Atlas, Blend, Flip, Image, ImageViewMut};
 use crate::power::FrogPower;
 use shipyard::{AllStoragesViewMut, UniqueView, UniqueViewMut};
 use std::collections::HashMap;
@@ -38,7 +40,7 @@ impl Rect {
     }
 }
 
-pub(crate) fn add_tilemap(mut storages: AllStoragesViewMut, tmx: &str) {
+pub(crate) fn add_tilemap(storages: &mut AllStoragesViewMut, tmx: &str) {
     let tmx = tiled::parse(Cursor::new(tmx)).unwrap();
     let mut shapes = Vec::new();
 
@@ -72,7 +74,7 @@ pub(crate) fn add_tilemap(mut storages: AllStoragesViewMut, tmx: &str) {
         // TODO: hardcoding group names for now
         match group.name.as_str() {
             "Collision" => load_collision_shapes(&mut shapes, dst_size, &group.objects),
-            "Entities" => load_entities(&mut storages, dst_size, &group.objects),
+            "Entities" => load_entities(storages, dst_size, &group.objects),
             _ => {
                 panic!("Group name {} is not supported", group.name);
             }
@@ -82,8 +84,7 @@ pub(crate) fn add_tilemap(mut storages: AllStoragesViewMut, tmx: &str) {
     let mut layers = Vec::new();
     for layer in &tmx.layers {
         if let LayerData::Finite(rows) = &layer.tiles {
-            let mut image = Vec::with_capacity((layer_width * layer_height * 4) as usize);
-            image.resize(image.capacity(), 0);
+            let mut image = vec![0; (layer_width * layer_height * 4) as usize];
             let mut dest = ImageViewMut::new(&mut image, dst_size);
 
             for (dst_y, cols) in rows.iter().enumerate() {
@@ -103,7 +104,15 @@ pub(crate) fn add_tilemap(mut storages: AllStoragesViewMut, tmx: &str) {
                     let dest_pos = Vec2::new(dst_x as f32, dst_y as f32) * tile_size;
                     let src_pos = Vec2::new(x as f32, y as f32) * tile_size;
 
-                    blit(&mut dest, dest_pos, &src, src_pos, tile_size, 1.0);
+                    blit(
+                        &mut dest,
+                        dest_pos,
+                        &src,
+                        src_pos,
+                        tile_size,
+                        Blend::Alpha,
+                        Flip::empty(),
+                    );
                 }
             }
 
@@ -133,12 +142,20 @@ pub(crate) fn add_tilemap(mut storages: AllStoragesViewMut, tmx: &str) {
 
     let viewport = Viewport {
         pos: Vec2::default(),
+        prev_pos: Vec2::default(),
         world_height: layer_height as f32,
     };
     storages.add_unique(viewport);
 
     let collision = Collision { shapes };
     storages.add_unique(collision);
+
+    let ambient = match tmx.properties.get("ambient_light") {
+        Some(PropertyValue::FloatValue(ambient)) => *ambient,
+        Some(PropertyValue::IntValue(ambient)) => *ambient as f32,
+        _ => 1.0,
+    };
+    storages.add_unique(Ambient(ambient));
 }
 
 fn get_parallax(properties: &HashMap<String, PropertyValue>) -> Vec2 {
@@ -178,6 +195,18 @@ fn load_collision_shapes(shapes: &mut Vec<Rect>, map_size: Vec2, objects: &[Obje
     }
 }
 
+/// Create a HUD portrait sprite showing the named frame.
+fn hud_sprite(name: &str) -> Sprite {
+    let atlas = Atlas::new(
+        include_bytes!("../assets/hud.png"),
+        include_str!("../assets/hud.json"),
+    );
+    let mut sprite = Sprite::new(atlas, Blend::Alpha);
+    sprite.frame_index = sprite.atlas.find(name).expect("Needs HUD frame");
+
+    sprite
+}
+
 fn load_entities(storages: &mut AllStoragesViewMut, map_size: Vec2, objects: &[Object]) {
     for object in objects {
         match (&object.shape, object.name.as_str()) {
@@ -190,26 +219,21 @@ fn load_entities(storages: &mut AllStoragesViewMut, map_size: Vec2, objects: &[O
             ) => {
                 let entity = match storages.borrow::<UniqueView<Hud>>() {
                     Err(_) => {
-                        // Create HUD
-                        // TODO: Needs a new intro
-                        // storages.add_unique(Hud::default());
-                        // None
-
-                        // XXX: Create HUD and frog sprite
+                        // Create HUD with the intro message, and the frog sprite
+                        let intro = storages
+                            .borrow::<UniqueView<Controls>>()
+                            .expect("Needs controls")
+                            .0
+                            .bindings()
+                            .intro();
                         storages.add_unique(Hud {
                             frog_power: Some(FrogPower::default()),
+                            message: Some(intro),
                             ..Default::default()
                         });
 
-                        let (width, height, image) =
-                            load_image(include_bytes!("../assets/hud.png"));
-                        let image = Image::new(image, Vec2::new(width as f32, height as f32));
                         let pos = Position(Vec3::new(3.0, 0.0, 13.0), CoordinateSystem::Screen);
-                        let sprite = Sprite {
-                            image,
-                            frame_height: 8,
-                            frame_index: 1,
-                        };
+                        let sprite = hud_sprite("frog");
 
                         Some((pos, sprite))
                     }
@@ -217,15 +241,8 @@ fn load_entities(storages: &mut AllStoragesViewMut, map_size: Vec2, objects: &[O
                         // Create any optional sprites
                         if hud.frog_power.is_some() {
                             eprintln!("Frog power!");
-                            let (width, height, image) =
-                                load_image(include_bytes!("../assets/hud.png"));
-                            let image = Image::new(image, Vec2::new(width as f32, height as f32));
                             let pos = Position(Vec3::new(3.0, 0.0, 13.0), CoordinateSystem::Screen);
-                            let sprite = Sprite {
-                                image,
-                                frame_height: 8,
-                                frame_index: 1,
-                            };
+                            let sprite = hud_sprite("frog");
 
                             Some((pos, sprite))
                         } else {
@@ -240,14 +257,8 @@ fn load_entities(storages: &mut AllStoragesViewMut, map_size: Vec2, objects: &[O
 
                 // Create sprite for Jean
                 {
-                    let (width, height, image) = load_image(include_bytes!("../assets/hud.png"));
-                    let image = Image::new(image, Vec2::new(width as f32, height as f32));
                     let pos = Position(Vec3::new(3.0, 0.0, 3.0), CoordinateSystem::Screen);
-                    let sprite = Sprite {
-                        image,
-                        frame_height: 8,
-                        frame_index: 0,
-                    };
+                    let sprite = hud_sprite("jean");
 
                     storages.add_entity((pos, sprite));
                 }
@@ -257,7 +268,7 @@ fn load_entities(storages: &mut AllStoragesViewMut, map_size: Vec2, objects: &[O
                 assert!((object.height - height).abs() < f32::EPSILON);
 
                 let pos = Vec3::new(object.x + width / 2.0, 0.0, map_size.y - object.y - height);
-                storages.add_entity(entity::jean(pos));
+                storages.add_entity(entity::jean(pos, &object.properties));
             }
             (ObjectShape::Rect { width, height }, "Blob") => {
                 let mut random = storages
@@ -276,11 +287,15 @@ fn load_entities(storages: &mut AllStoragesViewMut, map_size: Vec2, objects: &[O
                     .expect("Need random");
 
                 let pos = Vec3::new(object.x + width / 2.0, 0.0, map_size.y - object.y - height);
-                let fire = entity::fire(pos, &mut random.0);
+                let fire = entity::fire(pos, &object.properties, &mut random.0);
                 drop(random);
 
                 storages.add_entity(fire);
             }
+            (ObjectShape::Rect { width, height }, "Emitter") => {
+                let pos = Vec3::new(object.x + width / 2.0, 0.0, map_size.y - object.y - height);
+                storages.add_entity(entity::emitter(pos, &object.properties));
+            }
             (shape, name) => {
                 panic!("Entity named {} not supported: {:?}", name, shape);
             }
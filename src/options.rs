@@ -0,0 +1,100 @@
+use crate::component::Random;
+use crate::replay::Replay;
+use anyhow::{anyhow, bail, Result};
+use log::{info, warn};
+use std::path::PathBuf;
+
+/// Command line options.
+#[derive(Default)]
+pub struct Options {
+    /// Random seed from `--seed` or the `SOMBERVALE_SEED` environment variable.
+    pub seed: Option<u64>,
+
+    /// Record input to a file with `--record`.
+    pub record: Option<PathBuf>,
+
+    /// Replay input from a file with `--replay`.
+    pub replay: Option<PathBuf>,
+
+    /// Stop after a number of simulation ticks with `--ticks`.
+    pub ticks: Option<u64>,
+}
+
+impl Options {
+    /// Parse options from the command line.
+    pub fn from_args() -> Result<Self> {
+        let mut options = Self::default();
+        let mut seed = std::env::var("SOMBERVALE_SEED").ok();
+
+        // Accepts both `--name value` and `--name=value`
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let value = || {
+                value
+                    .or_else(|| args.next())
+                    .ok_or_else(|| anyhow!("{} requires a value", name))
+            };
+
+            match name.as_str() {
+                "--seed" => seed = Some(value()?),
+                "--record" => options.record = Some(value()?.into()),
+                "--replay" => options.replay = Some(value()?.into()),
+                "--ticks" => {
+                    let ticks = value()?;
+                    let ticks = ticks
+                        .parse()
+                        .map_err(|_| anyhow!("Ticks must be an unsigned integer: {:?}", ticks))?;
+                    options.ticks = Some(ticks);
+                }
+                _ => bail!("Unknown argument {}", name),
+            }
+        }
+
+        if let Some(seed) = seed {
+            let seed = seed
+                .parse()
+                .map_err(|_| anyhow!("Seed must be an unsigned integer: {:?}", seed))?;
+            options.seed = Some(seed);
+        }
+        if options.record.is_some() && options.replay.is_some() {
+            bail!("Cannot record and replay at the same time");
+        }
+
+        Ok(options)
+    }
+
+    /// Load or start the input replay, and choose the random seed.
+    pub(crate) fn replay_and_seed(&self) -> Result<(Option<Replay>, u64)> {
+        // Replays always use the seed they were recorded with
+        let (replay, seed) = match &self.replay {
+            Some(path) => {
+                let (replay, seed) = Replay::load(path)?;
+                if self.seed.is_some_and(|other| other != seed) {
+                    warn!("Ignoring seed; replay was recorded with seed {}", seed);
+                }
+                info!("Replaying input from {}", path.display());
+
+                (Some(replay), seed)
+            }
+            None => {
+                let seed = self.seed.unwrap_or_else(Random::entropy_seed);
+                let replay = match &self.record {
+                    Some(path) => {
+                        info!("Recording input to {}", path.display());
+                        Some(Replay::record(path, seed)?)
+                    }
+                    None => None,
+                };
+
+                (replay, seed)
+            }
+        };
+        info!("Random seed: {}", seed);
+
+        Ok((replay, seed))
+    }
+}
@@ -1,4 +1,4 @@
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 pub(crate) struct FrogPower {
     // Experience points
@@ -9,8 +9,9 @@ pub(crate) struct FrogPower {
     max_pp: usize,
     pp: usize,
 
+    // Game time when the cooldown started
     cooldown: Duration,
-    start: Instant,
+    start: Duration,
 }
 
 impl FrogPower {
@@ -21,26 +22,26 @@ impl FrogPower {
             max_pp: 1,
             pp: 1,
             cooldown: Duration::from_secs(3),
-            start: Instant::now(),
+            start: Duration::ZERO,
         }
     }
 
-    pub(crate) fn update(&mut self, frogs: usize) {
+    pub(crate) fn update(&mut self, frogs: usize, now: Duration) {
         // Increase the power meter when the number of live frogs is less than the player's pp
         if self.pp < self.max_pp
             && frogs < self.max_pp - self.pp
-            && self.start.elapsed() >= self.cooldown
+            && now.saturating_sub(self.start) >= self.cooldown
         {
-            self.start = Instant::now();
+            self.start = now;
             self.pp += 1;
         }
     }
 
-    pub(crate) fn use_power(&mut self) -> bool {
+    pub(crate) fn use_power(&mut self, now: Duration) -> bool {
         if self.pp > 0 {
             // Reset cooldown only when the meter is full
             if self.pp == self.max_pp {
-                self.start = Instant::now();
+                self.start = now;
             }
 
             self.pp -= 1;
@@ -83,3 +84,35 @@ impl Default for FrogPower {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::GameClock;
+
+    #[test]
+    fn test_frog_power_cooldown() {
+        let mut clock = GameClock::default();
+        let mut power = FrogPower::default();
+
+        clock.advance(Duration::from_secs(10));
+        assert!(power.use_power(clock.now()));
+        assert!(!power.use_power(clock.now()));
+
+        // The frog is gone, but the cooldown has not finished
+        clock.advance(Duration::from_millis(2999));
+        power.update(0, clock.now());
+        assert_eq!(power.pp(), 0);
+
+        // Refills one point after exactly 3 seconds
+        clock.advance(Duration::from_millis(1));
+        power.update(0, clock.now());
+        assert_eq!(power.pp(), 1);
+
+        // Live frogs hold the meter down
+        assert!(power.use_power(clock.now()));
+        clock.advance(Duration::from_secs(5));
+        power.update(1, clock.now());
+        assert_eq!(power.pp(), 0);
+    }
+}
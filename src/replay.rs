@@ -0,0 +1,233 @@
+use crate::control::{Actions, Direction, Power, Walk};
+use anyhow::{anyhow, ensure, Result};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// First line of a replay file.
+#[derive(Deserialize, Serialize)]
+struct Header {
+    seed: u64,
+}
+
+/// Actions that start on a simulation tick and last until the next record.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct Record {
+    tick: u64,
+
+    /// Walk direction bits, or 0 when not walking.
+    walk: u8,
+    power: Power,
+}
+
+/// Records or replays the actions fed into `Controls` on each simulation tick.
+///
+/// Replay files are JSON lines: a header with the random seed, followed by a record for every
+/// tick where the actions changed.
+pub(crate) enum Replay {
+    /// Keyboard actions are written whenever they change.
+    Record {
+        writer: Box<dyn Write + Send + Sync>,
+        last: Actions,
+    },
+
+    /// Actions are read from a replay instead of the keyboard.
+    Play {
+        records: Vec<Record>,
+        next: usize,
+        current: Actions,
+    },
+
+    /// Actions are generated from the tick number instead of the keyboard.
+    Script(fn(u64) -> Actions),
+}
+
+impl Record {
+    fn new(tick: u64, actions: Actions) -> Self {
+        let walk = match actions.walk {
+            Walk::NoInput => 0,
+            Walk::Walk(dir) => dir.bits(),
+        };
+
+        Self {
+            tick,
+            walk,
+            power: actions.power,
+        }
+    }
+
+    fn actions(&self) -> Actions {
+        let walk = match Direction::from_bits(self.walk) {
+            Some(dir) if !dir.is_empty() => Walk::Walk(dir),
+            _ => Walk::NoInput,
+        };
+
+        Actions {
+            walk,
+            power: self.power,
+        }
+    }
+}
+
+impl Replay {
+    /// Start recording to a file.
+    pub(crate) fn record(path: &Path, seed: u64) -> Result<Self> {
+        let file = File::create(path)?;
+
+        Self::record_to(Box::new(BufWriter::new(file)), seed)
+    }
+
+    fn record_to(mut writer: Box<dyn Write + Send + Sync>, seed: u64) -> Result<Self> {
+        write_line(&mut writer, &Header { seed })?;
+
+        Ok(Self::Record {
+            writer,
+            last: Actions::default(),
+        })
+    }
+
+    /// Load a replay file, returning the replay and the random seed it was recorded with.
+    pub(crate) fn load(path: &Path) -> Result<(Self, u64)> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(text: &str) -> Result<(Self, u64)> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.next().ok_or_else(|| anyhow!("Replay is empty"))?;
+        let header: Header = serde_json::from_str(header)?;
+
+        let mut records: Vec<Record> = Vec::new();
+        for line in lines {
+            let record: Record = serde_json::from_str(line)?;
+            ensure!(
+                Direction::from_bits(record.walk).is_some(),
+                "Invalid walk direction in replay: {}",
+                line
+            );
+            if let Some(prev) = records.last() {
+                ensure!(
+                    prev.tick < record.tick,
+                    "Replay ticks out of order: {}",
+                    line
+                );
+            }
+            records.push(record);
+        }
+
+        let replay = Self::Play {
+            records,
+            next: 0,
+            current: Actions::default(),
+        };
+
+        Ok((replay, header.seed))
+    }
+
+    /// Actions for a simulation tick, given the actions from the keyboard.
+    pub(crate) fn actions(&mut self, tick: u64, keyboard: Actions) -> Actions {
+        match self {
+            Self::Record { writer, last } => {
+                if keyboard != *last {
+                    *last = keyboard;
+                    if let Err(err) = write_line(writer, &Record::new(tick, keyboard)) {
+                        error!("Input recording failed: {}", err);
+                    }
+                }
+
+                keyboard
+            }
+            Self::Play {
+                records,
+                next,
+                current,
+            } => {
+                while let Some(record) = records.get(*next).filter(|record| record.tick <= tick) {
+                    *current = record.actions();
+                    *next += 1;
+                }
+
+                *current
+            }
+            Self::Script(script) => script(tick),
+        }
+    }
+}
+
+/// Write a JSON line and flush it, so the recording survives a crash.
+fn write_line<T: Serialize>(writer: &mut impl Write, value: &T) -> Result<()> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let walk = |dir| Actions {
+            walk: Walk::Walk(dir),
+            power: Power::NoInput,
+        };
+        let summon = Actions {
+            walk: Walk::Walk(Direction::UP_LEFT),
+            power: Power::Use,
+        };
+        let inputs = [
+            (1, Actions::default()),
+            (2, walk(Direction::UP)),
+            (3, walk(Direction::UP)),
+            (4, walk(Direction::UP_LEFT)),
+            (5, summon),
+            (6, Actions::default()),
+            (7, Actions::default()),
+        ];
+
+        let buffer = SharedBuffer::default();
+        let mut recording = Replay::record_to(Box::new(buffer.clone()), 42).unwrap();
+        for &(tick, actions) in &inputs {
+            assert_eq!(recording.actions(tick, actions), actions);
+        }
+
+        // Only changes are recorded
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(text.lines().count(), 5);
+
+        // The keyboard is ignored during replay
+        let (mut replay, seed) = Replay::parse(&text).unwrap();
+        assert_eq!(seed, 42);
+        for &(tick, actions) in &inputs {
+            assert_eq!(replay.actions(tick, summon), actions, "tick {}", tick);
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_replays() {
+        assert!(Replay::parse("").is_err());
+        assert!(Replay::parse("{\"seed\":1}\n{\"tick\":1,\"walk\":16,\"power\":\"use\"}").is_err());
+
+        let text = "{\"seed\":1}\n{\"tick\":2,\"walk\":1,\"power\":\"use\"}\n\
+            {\"tick\":1,\"walk\":0,\"power\":\"no_input\"}";
+        assert!(Replay::parse(text).is_err());
+    }
+}
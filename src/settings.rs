@@ -0,0 +1,319 @@
+use crate::control::Bindings;
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+// Volume hotkeys change levels in this many steps
+const VOLUME_STEPS: f64 = 10.0;
+
+/// User settings, persisted to `sombervale/settings.toml` in the user's config directory.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct Settings {
+    pub(crate) volume: Volume,
+    pub(crate) controls: Bindings,
+
+    // Set when the settings file could not be loaded, so saving does not overwrite it
+    #[serde(skip)]
+    invalid: bool,
+
+    // Volume as last loaded or saved, so saving only writes what has changed since
+    #[serde(skip)]
+    saved: Volume,
+}
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct Volume {
+    pub(crate) master: Bus,
+    pub(crate) music: Bus,
+    pub(crate) sfx: Bus,
+}
+
+/// Volume and mute for a group of sounds.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub(crate) struct Bus {
+    #[serde(deserialize_with = "level")]
+    pub(crate) level: f64,
+    pub(crate) muted: bool,
+}
+
+impl Settings {
+    /// Load settings from the config directory, or use defaults when there are none.
+    ///
+    /// Defaults are also used when the settings file is invalid, and the file is then left alone
+    /// for the player to fix instead of being overwritten by [`Settings::save`].
+    pub(crate) fn load() -> Self {
+        let path = match path() {
+            Ok(path) if path.exists() => path,
+            _ => return Self::default(),
+        };
+
+        match fs::read_to_string(&path).map_err(anyhow::Error::from) {
+            Ok(text) => match Self::parse(&text) {
+                Ok(settings) => {
+                    info!("Loaded settings from {}", path.display());
+                    settings
+                }
+                Err(err) => {
+                    warn!("Ignoring invalid settings in {}: {}", path.display(), err);
+                    Self::invalid()
+                }
+            },
+            Err(err) => {
+                warn!("Failed to read {}: {}", path.display(), err);
+                Self::invalid()
+            }
+        }
+    }
+
+    fn invalid() -> Self {
+        Self {
+            invalid: true,
+            ..Self::default()
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let mut settings: Self = toml::from_str(text)?;
+        settings.saved = settings.volume;
+
+        Ok(settings)
+    }
+
+    /// Save the volume settings to the config directory.
+    ///
+    /// Only the volume values changed since loading are written, so the rest of the player's file
+    /// is kept as written.
+    pub(crate) fn save(&mut self) -> Result<()> {
+        let path = path()?;
+        if self.invalid {
+            return Err(anyhow!(
+                "Not overwriting invalid settings in {}",
+                path.display()
+            ));
+        }
+
+        let text = if path.exists() {
+            fs::read_to_string(&path)?
+        } else {
+            String::new()
+        };
+        let text = self.merge(&text)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, text)?;
+        self.saved = self.volume;
+
+        Ok(())
+    }
+
+    /// Write the changed volume values into the settings file `text`.
+    fn merge(&self, text: &str) -> Result<String> {
+        let mut value: toml::Value = toml::from_str(text)?;
+        let buses = [
+            ("master", self.volume.master, self.saved.master),
+            ("music", self.volume.music, self.saved.music),
+            ("sfx", self.volume.sfx, self.saved.sfx),
+        ];
+        for (name, bus, saved) in buses.iter() {
+            if bus.level != saved.level {
+                table(&mut value, &["volume", name])?
+                    .insert("level".to_string(), toml::Value::Float(bus.level));
+            }
+            if bus.muted != saved.muted {
+                table(&mut value, &["volume", name])?
+                    .insert("muted".to_string(), toml::Value::Boolean(bus.muted));
+            }
+        }
+
+        Ok(toml::to_string(&value)?)
+    }
+}
+
+impl Bus {
+    /// Output volume, taking mute into account.
+    pub(crate) fn volume(&self) -> f64 {
+        if self.muted {
+            0.0
+        } else {
+            self.level
+        }
+    }
+
+    /// Turn the level up or down by a step, and unmute.
+    pub(crate) fn step(&mut self, up: bool) {
+        let step = if up { 1.0 } else { -1.0 };
+        let level = (self.level * VOLUME_STEPS + step).round() / VOLUME_STEPS;
+
+        self.level = level.clamp(0.0, 1.0);
+        self.muted = false;
+    }
+
+    pub(crate) fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self {
+            level: 1.0,
+            muted: false,
+        }
+    }
+}
+
+/// Deserialize a volume level, clamped to the range the hotkeys can reach.
+fn level<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let level = f64::deserialize(deserializer)?;
+
+    Ok(if level.is_nan() {
+        Bus::default().level
+    } else {
+        level.clamp(0.0, 1.0)
+    })
+}
+
+/// Get the table at `keys` in `value`, creating any that are missing.
+fn table<'a>(value: &'a mut toml::Value, keys: &[&str]) -> Result<&'a mut toml::value::Table> {
+    let mut table = value
+        .as_table_mut()
+        .ok_or_else(|| anyhow!("Settings are not a table"))?;
+    for key in keys {
+        table = table
+            .entry(*key)
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .ok_or_else(|| anyhow!("Settings {} is not a table", key))?;
+    }
+
+    Ok(table)
+}
+
+fn path() -> Result<PathBuf> {
+    let dir = dirs::config_dir().ok_or_else(|| anyhow!("No config directory"))?;
+
+    Ok(dir.join("sombervale").join("settings.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::Binding;
+    use winit::event::VirtualKeyCode;
+
+    #[test]
+    fn test_settings_round_trip() {
+        let mut settings = Settings::default();
+        settings.volume.music.step(false);
+        settings.volume.sfx.toggle_mute();
+
+        let text = toml::to_string(&settings).unwrap();
+        assert_eq!(Settings::parse(&text).unwrap().volume, settings.volume);
+
+        // Missing values use defaults
+        let settings = Settings::parse("[volume.music]\nlevel = 0.5\n").unwrap();
+        assert_eq!(settings.volume.music.level, 0.5);
+        assert!(!settings.volume.music.muted);
+        assert_eq!(settings.volume.master, Bus::default());
+        assert!(Settings::parse("[volume]\nmaster = 1").is_err());
+
+        // Levels are clamped
+        let settings =
+            Settings::parse("[volume.music]\nlevel = 5.0\n[volume.sfx]\nlevel = -1.0").unwrap();
+        assert_eq!(settings.volume.music.level, 1.0);
+        assert_eq!(settings.volume.sfx.level, 0.0);
+
+        let settings =
+            Settings::parse("[controls]\nsummon_frog = [\"Return\", \"scancode:57\"]").unwrap();
+        assert_eq!(settings.controls.summon_frog.len(), 2);
+        assert_eq!(settings.controls.move_up, Bindings::default().move_up);
+
+        // Unknown keys are skipped without losing the rest of the file
+        let settings =
+            Settings::parse("[volume.sfx]\nmuted = true\n[controls]\nmove_up = [\"Upp\", \"W\"]")
+                .unwrap();
+        assert!(settings.volume.sfx.muted);
+        assert_eq!(settings.controls.move_up, [Binding::Key(VirtualKeyCode::W)]);
+    }
+
+    #[test]
+    fn test_save_keeps_controls() {
+        let text = "[controls]\nmove_up = [\"Upp\", \"W\"]\nhop = [\"Space\"]\n";
+        let mut settings = Settings::parse(text).unwrap();
+        settings.volume.music.step(false);
+
+        let text = settings.merge(text).unwrap();
+        let value: toml::Value = toml::from_str(&text).unwrap();
+        assert_eq!(
+            value["controls"]["move_up"],
+            toml::Value::from(vec!["Upp", "W"])
+        );
+        assert_eq!(value["controls"]["hop"], toml::Value::from(vec!["Space"]));
+        assert_eq!(Settings::parse(&text).unwrap().volume, settings.volume);
+
+        // A new file only holds the volume
+        let text = Settings::default().merge("").unwrap();
+        assert!(!text.contains("controls"));
+    }
+
+    #[test]
+    fn test_save_changed_volume() {
+        let text = "[volume.master]
+level = 5.0
+[volume.sfx]
+level = 0.5
+muted = true
+";
+        let mut settings = Settings::parse(text).unwrap();
+        settings.volume.sfx.step(true);
+
+        // Values the player did not change are kept as written, even when out of range
+        let text = settings.merge(text).unwrap();
+        let value: toml::Value = toml::from_str(&text).unwrap();
+        assert_eq!(value["volume"]["master"]["level"], toml::Value::Float(5.0));
+        assert_eq!(value["volume"]["sfx"]["level"], toml::Value::Float(0.6));
+        assert_eq!(value["volume"]["sfx"]["muted"], toml::Value::Boolean(false));
+        assert!(value["volume"].get("music").is_none());
+
+        let mut settings = Settings::default();
+        settings.volume.music.toggle_mute();
+        let text = settings.merge("").unwrap();
+        assert_eq!(text, "[volume.music]\nmuted = true\n");
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        assert!(Settings::invalid().save().is_err());
+    }
+
+    #[test]
+    fn test_bus() {
+        let mut bus = Bus::default();
+        bus.step(true);
+        assert_eq!(bus.volume(), 1.0);
+
+        bus.toggle_mute();
+        assert_eq!(bus.volume(), 0.0);
+
+        // Changing the level unmutes
+        for _ in 0..3 {
+            bus.step(false);
+        }
+        assert_eq!(bus.volume(), 0.7);
+
+        for _ in 0..10 {
+            bus.step(false);
+        }
+        assert_eq!(bus.volume(), 0.0);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::audio::Player;
+use crate::clock::{GameClock, TIMESTEP};
+use crate::component::{self, Audio, Jean, Outro, Random, Stats};
+use crate::control::{Actions, Power, Walk};
+use crate::hud::Hud;
+use crate::options::Options;
+use crate::replay::Replay;
+use crate::system::register_systems;
+use crate::world::load_world;
+use anyhow::{ensure, Result};
+use shipyard::{AllStoragesViewMut, UniqueView, UniqueViewMut, View, World};
+use std::fmt;
+
+// Ticks between summon button presses for the scripted player
+const SUMMON_INTERVAL: u64 = 30;
+
+/// Runs the game without a window or rendering, and with silent audio.
+pub struct Simulation {
+    world: World,
+    seed: u64,
+}
+
+/// Results of a simulation run.
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub seed: u64,
+    pub ticks: u64,
+    pub frogs_summoned: u32,
+    pub shadows_annihilated: u32,
+    pub deaths: u32,
+    pub jean_alive: bool,
+
+    /// Power points, max power points, experience and max experience.
+    pub frog_power: Option<[usize; 4]>,
+}
+
+impl Simulation {
+    /// Input is replayed from `--replay` when it is given. Otherwise Jean stands still and
+    /// summons a frog whenever possible.
+    pub fn new(options: &Options) -> Result<Self> {
+        ensure!(
+            options.record.is_none(),
+            "Simulations cannot record input; use --replay"
+        );
+
+        let (replay, seed) = options.replay_and_seed()?;
+        let replay = replay.unwrap_or(Replay::Script(summon_script));
+
+        let world = World::default();
+        let storages = world.borrow::<AllStoragesViewMut>().unwrap();
+        storages.add_unique_non_sync(Audio(Player::null()));
+        storages.add_unique(component::Replay(replay));
+        load_world(storages, Random::from_seed(seed));
+        register_systems(&world);
+
+        Ok(Self { world, seed })
+    }
+
+    /// Run the "update" workload for one fixed tick.
+    pub fn step(&mut self) {
+        self.world
+            .borrow::<UniqueViewMut<GameClock>>()
+            .expect("Needs GameClock")
+            .advance(TIMESTEP);
+        self.world.run_workload("update").expect("update workload");
+    }
+
+    pub fn summary(&self) -> Summary {
+        let clock = self
+            .world
+            .borrow::<UniqueView<GameClock>>()
+            .expect("Needs GameClock");
+        let stats = self
+            .world
+            .borrow::<UniqueView<Stats>>()
+            .expect("Needs Stats");
+        let jean = self.world.borrow::<View<Jean>>().expect("Needs Jean");
+        let outro = self.world.borrow::<UniqueView<Outro>>();
+        let hud = self.world.borrow::<UniqueView<Hud>>();
+
+        let frog_power = hud.ok().and_then(|hud| {
+            hud.frog_power
+                .as_ref()
+                .map(|power| [power.pp(), power.max_pp(), power.xp(), power.max_xp()])
+        });
+
+        Summary {
+            seed: self.seed,
+            ticks: clock.ticks(),
+            frogs_summoned: stats.frogs_summoned,
+            shadows_annihilated: stats.shadows_annihilated,
+            deaths: stats.deaths,
+            jean_alive: !jean.is_empty() && outro.is_err(),
+            frog_power,
+        }
+    }
+}
+
+/// Tap the summon button every `SUMMON_INTERVAL` ticks.
+fn summon_script(tick: u64) -> Actions {
+    let power = if tick % SUMMON_INTERVAL == 0 {
+        Power::Use
+    } else {
+        Power::NoInput
+    };
+
+    Actions {
+        walk: Walk::NoInput,
+        power,
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = TIMESTEP.as_secs_f64() * self.ticks as f64;
+        writeln!(f, "Seed:                {}", self.seed)?;
+        writeln!(f, "Ticks:               {} ({:.1}s)", self.ticks, seconds)?;
+        writeln!(f, "Frogs summoned:      {}", self.frogs_summoned)?;
+        writeln!(f, "Shadows annihilated: {}", self.shadows_annihilated)?;
+        writeln!(f, "Deaths:              {}", self.deaths)?;
+        writeln!(
+            f,
+            "Jean:                {}",
+            if self.jean_alive { "alive" } else { "dead" }
+        )?;
+        match self.frog_power {
+            Some([pp, max_pp, xp, max_xp]) => write!(
+                f,
+                "Frog power:          {}/{} pp, {}/{} xp",
+                pp, max_pp, xp, max_xp
+            ),
+            None => write!(f, "Frog power:          none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shipyard::NonSync;
+
+    fn simulate(seed: u64, ticks: u64) -> Summary {
+        let options = Options {
+            seed: Some(seed),
+            ..Options::default()
+        };
+        let mut simulation = Simulation::new(&options).unwrap();
+        for _ in 0..ticks {
+            simulation.step();
+        }
+
+        simulation.summary()
+    }
+
+    #[test]
+    fn test_simulation_is_deterministic() {
+        let summary = simulate(7, 600);
+        assert_eq!(summary.ticks, 600);
+        assert!(summary.frogs_summoned > 0);
+
+        assert_eq!(summary, simulate(7, 600));
+    }
+
+    #[test]
+    fn test_simulation_sounds() {
+        let options = Options {
+            seed: Some(7),
+            ..Options::default()
+        };
+        let mut simulation = Simulation::new(&options).unwrap();
+        for _ in 0..120 {
+            simulation.step();
+        }
+
+        let audio = simulation
+            .world
+            .borrow::<NonSync<UniqueView<Audio>>>()
+            .unwrap();
+        assert_eq!(audio.0.requested()[..2], ["heartbeat", "music"]);
+
+        // Summoned frogs hop toward Jean
+        assert!(audio.0.requested().iter().any(|sound| sound == "jump"));
+    }
+
+    #[test]
+    fn test_simulation_duck() {
+        let options = Options {
+            seed: Some(7),
+            ..Options::default()
+        };
+        let mut simulation = Simulation::new(&options).unwrap();
+        for _ in 0..600 {
+            simulation.step();
+        }
+
+        // Summoning ducks the music, and blobs landing nearby do not
+        let audio = simulation
+            .world
+            .borrow::<NonSync<UniqueView<Audio>>>()
+            .unwrap();
+        let requested = audio.0.requested();
+        assert!(requested.iter().any(|sound| sound == "splat"));
+        let summons = requested.iter().filter(|sound| *sound == "summon").count();
+        assert!(summons > 0);
+        assert_eq!(audio.0.ducked_by(), vec!["summon"; summons].as_slice());
+    }
+}
@@ -1,21 +1,24 @@
-use crate::animation::{Animated, BlobAnims, FireAnims, FrogAnims, JeanAnims};
+use crate::clock::GameClock;
+use crate::color;
 use crate::component::{
-    Animation, Annihilate, Audio, Collision, Controls, CoordinateSystem, Follow, Outro, Position,
-    Random, Sprite, Tilemap, UpdateTime, Velocity, Viewport,
+    Ambient, Animation, Annihilate, Audio, Blob, Capture, Collision, Controls, CoordinateSystem,
+    CosmeticRandom, Emitter, Follow, Frame, Frog, Jean, Light, Outro, Position, PrevPosition,
+    Random, Replay, Sprite, Stats, Tilemap, Velocity, Viewport,
 };
 use crate::control::{Direction, Power, Walk};
 use crate::hud::Hud;
-use crate::image::{blit, ImageViewMut};
+use crate::image::{blit, Blend, Flip, ImageViewMut};
+use crate::light::LightMap;
 use crate::world::load_world;
 use crate::{HEIGHT, WIDTH};
-use pixels::Pixels;
+use log::info;
 use shipyard::{
     AllStoragesViewMut, EntitiesViewMut, Get, IntoFastIter, IntoWithId, NonSync, UniqueView,
     UniqueViewMut, View, ViewMut, Workload, World,
 };
 use std::f32::consts::TAU;
-use std::time::{Duration, Instant};
-use ultraviolet::{Rotor3, Vec2, Vec3};
+use std::time::Duration;
+use ultraviolet::{Lerp, Rotor3, Vec2, Vec3};
 
 // Speeds are in pixels per second
 const JEAN_SPEED: f32 = 60.0;
@@ -31,6 +34,9 @@ const FROG_THRESHOLD_JITTER: f32 = 4.0;
 // Minimum distance where a frog will begin hopping toward and annihilate a shadow creature
 const FROG_SHADOW_THRESHOLD: f32 = 2304.0; // 48 squared
 
+// Music changes to the threat state while a shadow creature is within this distance of Jean
+const MUSIC_THREAT_THRESHOLD: f32 = 4096.0; // 64 squared
+
 // Most entities have this radius (used for collision detection)
 const ENTITY_RADIUS: f32 = 5.0;
 
@@ -41,69 +47,86 @@ const BOUNDS_MAX: Vec2 = Vec2::new(WIDTH as f32 - BOUNDS_MIN.x, HEIGHT as f32 -
 const OUTRO_TIME: Duration = Duration::from_secs(2);
 
 type FrogStorage<'a> = (
+    ViewMut<'a, Frog>,
     ViewMut<'a, Position>,
+    ViewMut<'a, PrevPosition>,
     ViewMut<'a, Velocity>,
     ViewMut<'a, Sprite>,
-    ViewMut<'a, Animation<FrogAnims>>,
+    ViewMut<'a, Animation>,
     ViewMut<'a, Follow>,
+    ViewMut<'a, Light>,
 );
 
 pub(crate) fn register_systems(world: &World) {
     Workload::builder("draw")
-        .with_system(&draw_tilemap)
-        .with_system(&draw_sprite)
-        .with_system(&draw_hud)
+        .with_system(draw_tilemap)
+        .with_system(draw_sprite)
+        .with_system(draw_lighting)
+        .with_system(draw_screen_sprite)
+        .with_system(draw_hud)
+        .with_system(draw_fade)
+        .with_system(capture_frame)
         .add_to_world(world)
         .expect("Register systems");
 
     Workload::builder("update")
-        .with_system(&summon_frog)
-        .with_system(&update_jean_velocity)
-        .with_system(&update_frog_velocity)
-        .with_system(&update_blob_velocity)
-        .with_system(&update_positions)
-        .with_system(&update_jean_shadow_collision)
-        .with_system(&update_viewport)
-        .with_system(&update_animation::<JeanAnims>)
-        .with_system(&update_animation::<FrogAnims>)
-        .with_system(&update_animation::<BlobAnims>)
-        .with_system(&update_animation::<FireAnims>)
-        .with_system(&update_hud)
-        .with_system(&update_outro)
-        .with_system(&cleanup)
-        .with_system(&update_time)
+        .with_system(store_prev_positions)
+        .with_system(update_controls)
+        .with_system(summon_frog)
+        .with_system(update_jean_velocity)
+        .with_system(update_frog_velocity)
+        .with_system(update_blob_velocity)
+        .with_system(update_positions)
+        .with_system(update_jean_shadow_collision)
+        .with_system(update_viewport)
+        .with_system(update_animation)
+        .with_system(play_animation_sounds)
+        .with_system(update_music)
+        .with_system(update_emitters)
+        .with_system(update_lights)
+        .with_system(update_hud)
+        .with_system(update_outro)
+        .with_system(cleanup)
         .add_to_world(world)
         .expect("Register systems");
 }
 
 /// Convert world coordinates to screen coordinates.
-fn world_to_screen(pos: Vec3, size: Vec2, viewport: &Viewport) -> Vec2 {
-    let x = pos.x - size.x / 2.0;
-    let y = viewport.world_height - (pos.z + size.y);
+///
+/// The `pivot` is an offset from the upper left corner of the screen-space result, e.g. the
+/// pivot of a sprite frame.
+fn world_to_screen(pos: Vec3, pivot: Vec2, viewport: &Viewport) -> Vec2 {
+    let x = pos.x - pivot.x;
+    let y = viewport.world_height - (pos.z + pivot.y);
     let mut viewport_pos = viewport.pos;
     viewport_pos.apply(f32::floor);
     Vec2::new(x.floor(), y.floor()) - viewport_pos
 }
 
+/// Offset from the listener at the center of the screen, for positional audio.
+fn listener_offset(pos: Vec3, viewport: &Viewport) -> Vec2 {
+    world_to_screen(pos, Vec2::zero(), viewport) - SCREEN_SIZE / 2.0
+}
+
+/// Position to render, between the previous and current simulation step.
+fn interpolate(pos: &Position, prev: Option<&PrevPosition>, alpha: f32) -> Vec3 {
+    prev.map_or(pos.0, |prev| prev.0.lerp(pos.0, alpha))
+}
+
 fn draw_tilemap(
-    mut pixels: UniqueViewMut<Pixels>,
+    mut frame: UniqueViewMut<Frame>,
     viewport: UniqueView<Viewport>,
     tilemaps: View<Tilemap>,
-    outro: Option<UniqueView<Outro>>,
+    clock: UniqueView<GameClock>,
 ) {
-    let factor = if let Some(outro) = outro {
-        outro.1
-    } else {
-        1.0
-    };
+    let viewport = viewport.interpolate(clock.alpha());
 
     // Clear screen
-    let mut frame = pixels.get_frame();
-    for pixel in frame.chunks_exact_mut(4) {
+    for pixel in frame.0.chunks_exact_mut(4) {
         pixel.copy_from_slice(&[0, 0, 0, 0]);
     }
 
-    let mut dest = ImageViewMut::new(&mut frame, SCREEN_SIZE);
+    let mut dest = ImageViewMut::new(&mut frame.0, SCREEN_SIZE);
     let dest_pos = Vec2::default();
 
     for (layer,) in (&tilemaps,).fast_iter() {
@@ -115,64 +138,114 @@ fn draw_tilemap(
             &layer.image,
             src_pos,
             SCREEN_SIZE,
-            factor,
+            Blend::Alpha,
+            Flip::empty(),
         );
     }
 }
 
 fn draw_sprite(
-    mut pixels: UniqueViewMut<Pixels>,
+    mut frame: UniqueViewMut<Frame>,
     viewport: UniqueView<Viewport>,
     positions: View<Position>,
+    prev_positions: View<PrevPosition>,
     sprites: View<Sprite>,
-    outro: Option<UniqueView<Outro>>,
+    clock: UniqueView<GameClock>,
 ) {
-    let factor = if let Some(outro) = outro {
-        outro.1
-    } else {
-        1.0
-    };
-    let mut frame = pixels.get_frame();
+    let alpha = clock.alpha();
+    let viewport = viewport.interpolate(alpha);
+    let entities = (&positions, &sprites).fast_iter().with_id();
+    let entities = entities
+        .filter(|(_, (pos, _))| pos.1 == CoordinateSystem::World)
+        .map(|(id, (pos, sprite))| {
+            let pos = interpolate(pos, prev_positions.get(id).ok(), alpha);
+            (pos, sprite)
+        });
+
+    draw_sprites(&mut frame.0, &viewport, CoordinateSystem::World, entities);
+}
 
+fn draw_lighting(
+    mut frame: UniqueViewMut<Frame>,
+    viewport: UniqueView<Viewport>,
+    positions: View<Position>,
+    prev_positions: View<PrevPosition>,
+    lights: View<Light>,
+    ambient: Option<UniqueView<Ambient>>,
+    clock: UniqueView<GameClock>,
+) {
+    let ambient = ambient.map_or(1.0, |ambient| ambient.0);
+    let mut light_map = LightMap::new(SCREEN_SIZE, ambient);
+    let alpha = clock.alpha();
+    let viewport = viewport.interpolate(alpha);
+
+    for (id, (pos, light)) in (&positions, &lights).fast_iter().with_id() {
+        let pos = interpolate(pos, prev_positions.get(id).ok(), alpha);
+        let center = world_to_screen(pos, Vec2::new(0.0, light.0.height()), &viewport);
+        light_map.add(center, &light.0);
+    }
+
+    light_map.apply(&mut frame.0);
+}
+
+/// Screen space sprites are drawn separately so that they are not affected by lighting.
+fn draw_screen_sprite(
+    mut frame: UniqueViewMut<Frame>,
+    viewport: UniqueView<Viewport>,
+    positions: View<Position>,
+    sprites: View<Sprite>,
+) {
+    let entities = (&positions, &sprites).fast_iter();
+    let entities = entities
+        .filter(|(pos, _)| pos.1 == CoordinateSystem::Screen)
+        .map(|(pos, sprite)| (pos.0, sprite));
+
+    draw_sprites(&mut frame.0, &viewport, CoordinateSystem::Screen, entities);
+}
+
+fn draw_sprites<'a>(
+    frame: &mut [u8],
+    viewport: &Viewport,
+    coordinates: CoordinateSystem,
+    entities: impl Iterator<Item = (Vec3, &'a Sprite)>,
+) {
     // Create a single ImageViewMut that is shared over all sprites when debug mode is disabled
     #[cfg(not(feature = "debug-mode"))]
-    let mut dest = ImageViewMut::new(&mut frame, SCREEN_SIZE);
+    let mut dest = ImageViewMut::new(frame, SCREEN_SIZE);
 
     // Sort entities by Z coordinate
-    let mut entities = (&positions, &sprites).fast_iter().collect::<Vec<_>>();
-    entities.sort_unstable_by_key(|(pos, _)| -pos.0.z as i32);
+    let mut entities = entities.collect::<Vec<_>>();
+    entities.sort_unstable_by_key(|(pos, _)| -pos.z as i32);
 
     for (pos, sprite) in entities {
-        // Convert entity position to screen space
-        let frame_size = Vec2::new(sprite.image.size().x, sprite.frame_height as f32);
-        let dest_pos = if pos.1 == CoordinateSystem::World {
-            world_to_screen(pos.0, frame_size, &viewport)
+        // Convert entity position to screen space, placing the frame pivot on the position
+        let sprite_frame = sprite.frame();
+        let dest_pos = if coordinates == CoordinateSystem::World {
+            world_to_screen(pos, sprite_frame.pivot, viewport)
         } else {
-            Vec2::new(pos.0.x, pos.0.z)
+            Vec2::new(pos.x, pos.z) - sprite_frame.pivot
         };
 
-        // Select the current frame
-        let src_pos = Vec2::new(0.0, sprite.frame_index as f32 * sprite.frame_height as f32);
-
         // DEBUG: We need a temporary ImageViewMut so that we can draw directly to the buffer later
         #[cfg(feature = "debug-mode")]
-        let mut dest = ImageViewMut::new(&mut frame, SCREEN_SIZE);
+        let mut dest = ImageViewMut::new(frame, SCREEN_SIZE);
 
         // Copy source image to destination frame
         blit(
             &mut dest,
             dest_pos,
-            &sprite.image,
-            src_pos,
-            frame_size,
-            factor,
+            sprite.atlas.image(),
+            sprite_frame.pos,
+            sprite_frame.size,
+            sprite.blend,
+            sprite.flip,
         );
 
         // DEBUG DRAWING
         #[cfg(feature = "debug-mode")]
         {
             // Pink dot for upper left corner
-            let screen_pos = world_to_screen(pos.0, frame_size, &viewport);
+            let screen_pos = world_to_screen(pos, sprite_frame.pivot, viewport);
             let x = screen_pos.x as isize;
             let y = screen_pos.y as isize;
             let width = WIDTH as isize;
@@ -183,8 +256,7 @@ fn draw_sprite(
             }
 
             // Red dot for feet ("world position")
-            let screen_pos = world_to_screen(pos.0, frame_size, &viewport)
-                + Vec2::new(frame_size.x / 2.0, frame_size.y);
+            let screen_pos = world_to_screen(pos, Vec2::zero(), viewport);
             let x = screen_pos.x as isize;
             let y = screen_pos.y as isize;
             let width = WIDTH as isize;
@@ -197,20 +269,15 @@ fn draw_sprite(
     }
 }
 
-fn draw_hud(
-    mut pixels: UniqueViewMut<Pixels>,
-    hud: Option<UniqueView<Hud>>,
-    outro: Option<UniqueView<Outro>>,
-) {
-    let mut dest = ImageViewMut::new(pixels.get_frame(), Vec2::new(WIDTH as f32, HEIGHT as f32));
-    let factor = outro.map(|outro| outro.1).unwrap_or(1.0);
+fn draw_hud(mut frame: UniqueViewMut<Frame>, hud: Option<UniqueView<Hud>>) {
+    let mut dest = ImageViewMut::new(&mut frame.0, SCREEN_SIZE);
 
     if let Some(hud) = hud.as_ref() {
-        hud.draw(&mut dest, factor);
+        hud.draw(&mut dest);
 
         #[cfg(feature = "debug-mode")]
         {
-            const COLOR: &[u8; 4] = &[0, 0xff, 0, 0xff];
+            const COLOR: [u8; 4] = [0, 0xff, 0, 0xff];
 
             // Draw the viewport boundary box
             let lines = [
@@ -225,11 +292,24 @@ fn draw_hud(
                 ),
                 (Vec2::new(BOUNDS_MIN.x - 1.0, BOUNDS_MAX.y), BOUNDS_MAX),
             ];
-            image::lines(dest, Vec2::zero(), COLOR, &lines, factor);
+            crate::image::lines(&mut dest, Vec2::zero(), COLOR, &lines);
         }
     }
 }
 
+/// Fade the whole frame to black during the outro.
+fn draw_fade(mut frame: UniqueViewMut<Frame>, outro: Option<UniqueView<Outro>>) {
+    if let Some(outro) = outro {
+        color::tint(&mut frame.0, color::fade(outro.1));
+    }
+}
+
+fn capture_frame(frame: UniqueView<Frame>, capture: Option<UniqueViewMut<Capture>>) {
+    if let Some(mut capture) = capture {
+        capture.0.record(&frame.0);
+    }
+}
+
 fn summon_frog(storages: AllStoragesViewMut) {
     // Get all the storages we want to work with
     let mut entities = storages
@@ -242,16 +322,23 @@ fn summon_frog(storages: AllStoragesViewMut) {
     let mut random = storages
         .borrow::<UniqueViewMut<Random>>()
         .expect("Needs Random");
-    let tag = storages
-        .borrow::<ViewMut<Animation<JeanAnims>>>()
-        .expect("Needs Animation");
-    let storage = storages.borrow::<FrogStorage>().expect("Needs UpdateTime");
+    let tag = storages.borrow::<View<Jean>>().expect("Needs Jean");
+    let storage = storages.borrow::<FrogStorage>().expect("Needs FrogStorage");
     let collision = storages
         .borrow::<UniqueViewMut<Collision>>()
         .expect("Needs Collision");
+    let clock = storages
+        .borrow::<UniqueView<GameClock>>()
+        .expect("Needs GameClock");
+    let viewport = storages
+        .borrow::<UniqueView<Viewport>>()
+        .expect("Needs Viewport");
+    let mut audio = storages
+        .borrow::<NonSync<UniqueViewMut<Audio>>>()
+        .expect("Needs Audio");
 
     // Get Jean's position
-    let jean = (&storage.0, &tag)
+    let jean = (&storage.1, &tag)
         .fast_iter()
         .with_id()
         .next()
@@ -260,7 +347,7 @@ fn summon_frog(storages: AllStoragesViewMut) {
     // TODO: Select the correct power based on HUD
     if let Ok(mut hud) = hud {
         if let (Some((pos, jean_id)), Some(frog_power)) = (jean, hud.frog_power.as_mut()) {
-            if controls.0.power() == Power::Use && frog_power.use_power() {
+            if controls.0.power() == Power::Use && frog_power.use_power(clock.now()) {
                 let angle = random.next_f32_unit() * TAU;
 
                 // Avoid summoning the Frog inside a collision shape
@@ -283,42 +370,79 @@ fn summon_frog(storages: AllStoragesViewMut) {
                 };
 
                 let frog = crate::entity::frog(frog_pos, Follow::new(jean_id));
+                audio
+                    .0
+                    .play_at("summon", listener_offset(frog_pos, &viewport));
 
                 entities.add_entity(storage, frog);
+                storages
+                    .borrow::<UniqueViewMut<Stats>>()
+                    .expect("Needs Stats")
+                    .frogs_summoned += 1;
+
+                // The intro message has served its purpose
+                hud.message = None;
             }
         }
     }
 }
 
+pub(crate) fn store_prev_positions(
+    positions: View<Position>,
+    mut prev_positions: ViewMut<PrevPosition>,
+    mut viewport: UniqueViewMut<Viewport>,
+) {
+    for (pos, prev) in (&positions, &mut prev_positions).fast_iter() {
+        prev.0 = pos.0;
+    }
+
+    viewport.prev_pos = viewport.pos;
+}
+
+fn update_controls(
+    mut controls: UniqueViewMut<Controls>,
+    replay: Option<UniqueViewMut<Replay>>,
+    clock: UniqueView<GameClock>,
+) {
+    let keyboard = controls.0.actions();
+    let actions = match replay {
+        Some(mut replay) => replay.0.actions(clock.ticks(), keyboard),
+        None => keyboard,
+    };
+
+    controls.0.apply(actions);
+}
+
 fn update_jean_velocity(
     mut velocities: ViewMut<Velocity>,
     mut positions: ViewMut<Position>,
-    mut animations: ViewMut<Animation<JeanAnims>>,
+    mut animations: ViewMut<Animation>,
+    tags: View<Jean>,
     mut controls: UniqueViewMut<Controls>,
-    ut: UniqueView<UpdateTime>,
+    clock: UniqueView<GameClock>,
 ) {
-    use crate::animation::JeanCurrentAnim::*;
-
-    let dt = ut.0.elapsed();
+    let dt = clock.delta();
     let magnitude = Vec3::new(dt.as_secs_f32() / (1.0 / JEAN_SPEED), 0.0, 0.0);
-    let entities = (&mut velocities, &mut positions, &mut animations).fast_iter();
-
-    for (vel, pos, anim) in entities {
-        let (animation, angle) = match controls.0.walk() {
-            Walk::Walk(Direction::RIGHT) => (WalkRight, TAU * (0.0 / 8.0)),
-            Walk::Walk(Direction::UP_RIGHT) => (WalkRight, TAU * (1.0 / 8.0)),
-            Walk::Walk(Direction::UP) => (anim.0.to_walking(), TAU * (2.0 / 8.0)),
-            Walk::Walk(Direction::UP_LEFT) => (WalkLeft, TAU * (3.0 / 8.0)),
-            Walk::Walk(Direction::LEFT) => (WalkLeft, TAU * (4.0 / 8.0)),
-            Walk::Walk(Direction::DOWN_LEFT) => (WalkLeft, TAU * (5.0 / 8.0)),
-            Walk::Walk(Direction::DOWN) => (anim.0.to_walking(), TAU * (6.0 / 8.0)),
-            Walk::Walk(Direction::DOWN_RIGHT) => (WalkRight, TAU * (7.0 / 8.0)),
-            _ => (anim.0.to_idle(), -1.0),
+    let entities = (&mut velocities, &mut positions, &mut animations, &tags).fast_iter();
+
+    for (vel, pos, anim, _) in entities {
+        // Walking straight up or down keeps the current facing
+        let (left, angle) = match controls.0.walk() {
+            Walk::Walk(Direction::RIGHT) => (Some(false), TAU * (0.0 / 8.0)),
+            Walk::Walk(Direction::UP_RIGHT) => (Some(false), TAU * (1.0 / 8.0)),
+            Walk::Walk(Direction::UP) => (None, TAU * (2.0 / 8.0)),
+            Walk::Walk(Direction::UP_LEFT) => (Some(true), TAU * (3.0 / 8.0)),
+            Walk::Walk(Direction::LEFT) => (Some(true), TAU * (4.0 / 8.0)),
+            Walk::Walk(Direction::DOWN_LEFT) => (Some(true), TAU * (5.0 / 8.0)),
+            Walk::Walk(Direction::DOWN) => (None, TAU * (6.0 / 8.0)),
+            Walk::Walk(Direction::DOWN_RIGHT) => (Some(false), TAU * (7.0 / 8.0)),
+            _ => (None, -1.0),
         };
 
-        if anim.0.playing() != animation {
-            anim.0.set(animation);
+        if let Some(left) = left {
+            anim.0.face_left(left);
         }
+        anim.0.trigger(if angle >= 0.0 { "walk" } else { "stop" });
 
         if angle >= 0.0 {
             let rotor = Rotor3::from_rotation_xz(angle);
@@ -336,34 +460,38 @@ fn update_jean_velocity(
 }
 
 fn update_frog_velocity(storages: AllStoragesViewMut) {
-    use crate::animation::FrogCurrentAnim::*;
-
     // Get all the storages we want to work with
     let mut velocities = storages
         .borrow::<ViewMut<Velocity>>()
         .expect("Needs Velocity");
     let mut animations = storages
-        .borrow::<ViewMut<Animation<FrogAnims>>>()
+        .borrow::<ViewMut<Animation>>()
         .expect("Needs Animation");
+    let frogs = storages.borrow::<View<Frog>>().expect("Needs Frog");
     let mut following = storages.borrow::<ViewMut<Follow>>().expect("Needs Follow");
     let positions = storages.borrow::<View<Position>>().expect("Needs Position");
-    let ut = storages
-        .borrow::<UniqueView<UpdateTime>>()
-        .expect("Needs UpdateTime");
+    let clock = storages
+        .borrow::<UniqueView<GameClock>>()
+        .expect("Needs GameClock");
 
-    let dt = ut.0.elapsed();
+    let dt = clock.delta();
     let magnitude = dt.as_secs_f32() / (1.0 / FROG_SPEED);
-    let entities = (&mut velocities, &mut animations, &mut following, &positions).fast_iter();
-
-    for (frog_id, (vel, anim, follow, pos)) in entities.with_id() {
+    let entities = (
+        &mut velocities,
+        &mut animations,
+        &mut following,
+        &positions,
+        &frogs,
+    )
+        .fast_iter();
+
+    for (frog_id, (vel, anim, follow, pos, _)) in entities.with_id() {
         // Get Jean's position
         if let Ok(jean_pos) = positions.get(follow.entity_id) {
             // Position of Jean relative to Frog
             let relative_pos = jean_pos.0 - pos.0;
 
-            let shadows = storages
-                .borrow::<View<Animation<BlobAnims>>>()
-                .expect("Needs Blobs");
+            let shadows = storages.borrow::<View<Blob>>().expect("Needs Blobs");
 
             // Position relative to nearest shadow
             let (nearest_shadow_id, nearest_shadow_pos) =
@@ -388,6 +516,10 @@ fn update_frog_velocity(storages: AllStoragesViewMut) {
 
                 annihilate.0.push(frog_id);
                 annihilate.0.push(nearest_shadow_id.unwrap());
+                storages
+                    .borrow::<UniqueViewMut<Stats>>()
+                    .expect("Needs Stats")
+                    .shadows_annihilated += 1;
 
                 let mut hud = storages.borrow::<UniqueViewMut<Hud>>().expect("Needs HUD");
 
@@ -403,40 +535,23 @@ fn update_frog_velocity(storages: AllStoragesViewMut) {
             }
 
             // Update the direction only when the Frog is idling
-            if anim.0.playing() == IdleLeft || anim.0.playing() == IdleRight {
+            if anim.0.state() == "idle" {
                 let mut random = storages
                     .borrow::<UniqueViewMut<Random>>()
                     .expect("Needs Random");
-                let mut audio = storages
-                    .borrow::<NonSync<UniqueViewMut<Audio>>>()
-                    .expect("Needs Audio");
 
                 let jitter = random.next_f32_unit() * FROG_THRESHOLD_JITTER;
 
                 if nearest_shadow_mag < FROG_SHADOW_THRESHOLD {
                     // Frog is near a shadow creature
-                    let animation = if nearest_shadow_pos.x > 0.0 {
-                        HopRight
-                    } else {
-                        HopLeft
-                    };
-                    if anim.0.playing() != animation {
-                        anim.0.set(animation);
-                        audio.0.jump();
-                    }
+                    anim.0.face_left(nearest_shadow_pos.x <= 0.0);
+                    anim.0.trigger("hop");
 
                     follow.direction = nearest_shadow_pos.normalized();
                 } else if relative_pos.mag() - jitter > FROG_THRESHOLD {
                     // Frog is not near a shadow creature, but is far away from Jean
-                    let animation = if relative_pos.x > 0.0 {
-                        HopRight
-                    } else {
-                        HopLeft
-                    };
-                    if anim.0.playing() != animation {
-                        anim.0.set(animation);
-                        audio.0.jump();
-                    }
+                    anim.0.face_left(relative_pos.x <= 0.0);
+                    anim.0.trigger("hop");
 
                     let rotor = Rotor3::from_rotation_xz(random.next_f32_ndc() * TAU / 16.0);
                     follow.direction = relative_pos.normalized().rotated_by(rotor);
@@ -444,9 +559,15 @@ fn update_frog_velocity(storages: AllStoragesViewMut) {
             }
         }
 
-        // Frog ONLY moves when the animation frame is hopping
-        let frame_index = anim.0.get_frame_index();
-        vel.0 = if frame_index != 0 && frame_index != 4 && frame_index != 5 && frame_index != 9 {
+        // Frog ONLY moves between the "move" and "land" frames of a hop
+        for event in anim.0.events() {
+            match event {
+                "move" => follow.hopping = true,
+                "land" => follow.hopping = false,
+                _ => (),
+            }
+        }
+        vel.0 = if follow.hopping {
             follow.direction * magnitude
         } else {
             Vec3::default()
@@ -456,36 +577,27 @@ fn update_frog_velocity(storages: AllStoragesViewMut) {
 
 fn update_blob_velocity(
     mut velocities: ViewMut<Velocity>,
-    mut animations: ViewMut<Animation<BlobAnims>>,
+    mut animations: ViewMut<Animation>,
+    tags: View<Blob>,
     mut random: UniqueViewMut<Random>,
-    mut audio: NonSync<UniqueViewMut<Audio>>,
-    ut: UniqueView<UpdateTime>,
+    clock: UniqueView<GameClock>,
 ) {
-    use crate::animation::BlobCurrentAnim::*;
-
-    let dt = ut.0.elapsed();
+    let dt = clock.delta();
     let magnitude = dt.as_secs_f32() / (1.0 / BLOB_SPEED);
-    let entities = (&mut velocities, &mut animations).fast_iter();
+    let entities = (&mut velocities, &mut animations, &tags).fast_iter();
 
-    for (vel, anim) in entities {
+    for (vel, anim, _) in entities {
         // When not moving, randomly decide on a new direction to bounce
         if vel.0.mag_sq() < 0.01 && random.next_f32_unit() < 0.01 {
             let angle = random.next_f32_unit() * TAU;
             let rotor = Rotor3::from_rotation_xz(angle);
             vel.0 = Vec3::unit_x().rotated_by(rotor) * magnitude;
 
-            let animation = if vel.0.x > 0.0 {
-                BounceRight
-            } else {
-                BounceLeft
-            };
-            if anim.0.playing() != animation {
-                anim.0.set(animation);
-                audio.0.splat();
-            }
+            anim.0.face_left(vel.0.x <= 0.0);
+            anim.0.trigger("bounce");
         }
 
-        if let IdleLeft | IdleRight = anim.0.playing() {
+        if anim.0.state() == "idle" {
             vel.0 = Vec3::default();
         }
     }
@@ -494,18 +606,14 @@ fn update_blob_velocity(
 fn update_jean_shadow_collision(storages: AllStoragesViewMut) {
     // Get all the storages we want to work with
     let positions = storages.borrow::<View<Position>>().expect("Needs Position");
-    let jean = storages
-        .borrow::<View<Animation<JeanAnims>>>()
-        .expect("Needs Jean");
+    let jean = storages.borrow::<View<Jean>>().expect("Needs Jean");
 
     let mut it = (&positions, &jean)
         .fast_iter()
         .with_id()
         .map(|(id, (pos, _))| (id, pos));
     if let Some((jean_id, jean_pos)) = it.next() {
-        let shadows = storages
-            .borrow::<View<Animation<BlobAnims>>>()
-            .expect("Needs Blobs");
+        let shadows = storages.borrow::<View<Blob>>().expect("Needs Blobs");
         let entities = (&positions, &shadows).fast_iter();
 
         for (shadow_id, (shadow_pos, _)) in entities.with_id() {
@@ -517,20 +625,103 @@ fn update_jean_shadow_collision(storages: AllStoragesViewMut) {
                 annihilate.0.push(jean_id);
                 annihilate.0.push(shadow_id);
 
-                storages.add_unique(Outro(Instant::now(), 1.0));
+                let clock = storages
+                    .borrow::<UniqueView<GameClock>>()
+                    .expect("Needs GameClock");
+
+                // Only the first shadow to reach Jean counts
+                if storages.borrow::<UniqueView<Outro>>().is_err() {
+                    storages
+                        .borrow::<UniqueViewMut<Stats>>()
+                        .expect("Needs Stats")
+                        .deaths += 1;
+                }
+                storages.add_unique(Outro(clock.now(), 1.0));
             }
         }
     }
 }
 
-fn update_animation<A: Animated + 'static>(
-    mut animations: ViewMut<Animation<A>>,
+fn update_animation(
+    mut animations: ViewMut<Animation>,
     mut sprite: ViewMut<Sprite>,
+    clock: UniqueView<GameClock>,
 ) {
     let entities = (&mut animations, &mut sprite).fast_iter();
 
     for (anim, sprite) in entities {
-        sprite.frame_index = anim.0.animate();
+        sprite.frame_index = anim.0.animate(clock.delta());
+        sprite.flip = anim.0.flip();
+    }
+}
+
+fn play_animation_sounds(
+    animations: View<Animation>,
+    positions: View<Position>,
+    viewport: UniqueView<Viewport>,
+    clock: UniqueView<GameClock>,
+    mut audio: NonSync<UniqueViewMut<Audio>>,
+) {
+    audio.0.update(clock.now());
+
+    for (anim, pos) in (&animations, &positions).fast_iter() {
+        for event in anim.0.events() {
+            if let Some(name) = event.strip_prefix("play_sound:") {
+                audio.0.play_at(name, listener_offset(pos.0, &viewport));
+            }
+        }
+    }
+}
+
+fn update_music(
+    positions: View<Position>,
+    jean: View<Jean>,
+    shadows: View<Blob>,
+    outro: Option<UniqueView<Outro>>,
+    mut audio: NonSync<UniqueViewMut<Audio>>,
+) {
+    let threatened = (&positions, &jean).fast_iter().any(|(jean_pos, _)| {
+        (&positions, &shadows)
+            .fast_iter()
+            .any(|(shadow_pos, _)| (shadow_pos.0 - jean_pos.0).mag_sq() < MUSIC_THREAT_THRESHOLD)
+    });
+
+    let state = if outro.is_some() {
+        "outro"
+    } else if threatened {
+        "threat"
+    } else {
+        "calm"
+    };
+    audio.0.set_music(state);
+}
+
+fn update_emitters(
+    emitters: View<Emitter>,
+    positions: View<Position>,
+    viewport: UniqueView<Viewport>,
+    mut audio: NonSync<UniqueViewMut<Audio>>,
+) {
+    let emitters = (&emitters, &positions)
+        .fast_iter()
+        .with_id()
+        .map(|(id, (emitter, pos))| {
+            let offset = listener_offset(pos.0, &viewport);
+            (id, emitter.sound.as_str(), emitter.volume, offset)
+        });
+
+    audio.0.update_emitters(emitters);
+}
+
+fn update_lights(
+    mut lights: ViewMut<Light>,
+    mut random: UniqueViewMut<CosmeticRandom>,
+    clock: UniqueView<GameClock>,
+) {
+    let dt = clock.delta();
+
+    for light in (&mut lights).fast_iter() {
+        light.0.flicker(dt, &mut random.0);
     }
 }
 
@@ -554,21 +745,22 @@ fn update_positions(
     }
 }
 
-fn update_viewport(
+pub(crate) fn update_viewport(
     mut viewport: UniqueViewMut<Viewport>,
     positions: View<Position>,
     sprites: View<Sprite>,
-    tag: View<Animation<JeanAnims>>,
+    tag: View<Jean>,
 ) {
     // Viewport follows Jean
     for (pos, sprite, _) in (&positions, &sprites, &tag).fast_iter() {
         let viewport_basis = Viewport {
             pos: Vec2::default(),
+            prev_pos: Vec2::default(),
             world_height: viewport.world_height,
         };
-        let size = Vec2::new(sprite.image.size().x, sprite.frame_height as f32);
-        let sprite_min = world_to_screen(pos.0, size, &viewport_basis);
-        let sprite_max = sprite_min + size;
+        let sprite_frame = sprite.frame();
+        let sprite_min = world_to_screen(pos.0, sprite_frame.pivot, &viewport_basis);
+        let sprite_max = sprite_min + sprite_frame.size;
 
         // FIXME: Use clamp instead of multiple conditions
         if sprite_max.x > (viewport.pos.x + BOUNDS_MAX.x) {
@@ -589,10 +781,14 @@ fn update_viewport(
     }
 }
 
-fn update_hud(mut hud: Option<UniqueViewMut<Hud>>, frogs: View<Animation<FrogAnims>>) {
+fn update_hud(
+    mut hud: Option<UniqueViewMut<Hud>>,
+    frogs: View<Frog>,
+    clock: UniqueView<GameClock>,
+) {
     if let Some(hud) = hud.as_mut() {
         if let Some(frog_power) = &mut hud.frog_power {
-            frog_power.update(frogs.len());
+            frog_power.update(frogs.len(), clock.now());
         }
     }
 }
@@ -601,10 +797,19 @@ fn update_outro(mut storages: AllStoragesViewMut) {
     // Require an Outro
     let mut outro_result = storages.borrow::<UniqueViewMut<Outro>>();
     if let Ok(ref mut outro) = outro_result {
-        let elapsed = outro.0.elapsed();
+        let elapsed = storages
+            .borrow::<UniqueView<GameClock>>()
+            .expect("Needs GameClock")
+            .elapsed(outro.0);
         if elapsed >= OUTRO_TIME {
             drop(outro_result);
 
+            // The next world is seeded from this one, so a whole session can be reproduced
+            let seed = storages
+                .borrow::<UniqueViewMut<Random>>()
+                .expect("Needs Random")
+                .next_seed();
+
             // Remove everything
             storages.clear();
             storages.remove_unique::<Outro>().ok();
@@ -612,9 +817,12 @@ fn update_outro(mut storages: AllStoragesViewMut) {
             storages.remove_unique::<Collision>().ok();
             storages.remove_unique::<Annihilate>().ok();
             storages.remove_unique::<Hud>().ok();
+            storages.remove_unique::<Ambient>().ok();
+            storages.remove_unique::<Random>().ok();
 
             // Reload the map
-            load_world(storages);
+            info!("Reloading world with seed {}", seed);
+            load_world(storages, Random::from_seed(seed));
         } else {
             // Lerp the opacity
             outro.1 = ((OUTRO_TIME - elapsed).as_secs_f32() / OUTRO_TIME.as_secs_f32()).max(0.0);
@@ -636,6 +844,72 @@ fn cleanup(mut storages: AllStoragesViewMut) {
     }
 }
 
-fn update_time(mut dt: UniqueViewMut<UpdateTime>) {
-    dt.0 = Instant::now();
+#[cfg(test)]
+mod golden;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_without_window() {
+        let world = World::default();
+        load_world(
+            world.borrow::<AllStoragesViewMut>().unwrap(),
+            Random::default(),
+        );
+        register_systems(&world);
+
+        world.run_workload("draw").unwrap();
+
+        let frame = world.borrow::<UniqueView<Frame>>().unwrap();
+        assert_eq!(frame.0.len(), (WIDTH * HEIGHT * 4) as usize);
+        assert!(frame.0.chunks_exact(4).any(|pixel| pixel[3] != 0));
+
+        // The camera starts on Jean instead of moving there from the map origin
+        let viewport = world.borrow::<UniqueView<Viewport>>().unwrap();
+        assert_ne!(viewport.pos, Vec2::zero());
+        assert_eq!(viewport.prev_pos, viewport.pos);
+    }
+
+    #[test]
+    fn test_reload_is_deterministic() {
+        fn reload(seed: u64) -> u32 {
+            let world = World::default();
+            load_world(
+                world.borrow::<AllStoragesViewMut>().unwrap(),
+                Random::from_seed(seed),
+            );
+            world.add_unique(Outro(Duration::ZERO, 1.0)).unwrap();
+            world
+                .borrow::<UniqueViewMut<GameClock>>()
+                .unwrap()
+                .advance(OUTRO_TIME);
+
+            world.run(update_outro).unwrap();
+            assert!(world.borrow::<UniqueView<Outro>>().is_err());
+
+            let mut random = world.borrow::<UniqueViewMut<Random>>().unwrap();
+            random.next_u32()
+        }
+
+        assert_eq!(reload(42), reload(42));
+        assert_ne!(reload(42), reload(43));
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let pos = Position(Vec3::new(10.0, 0.0, 4.0), CoordinateSystem::World);
+        let prev = PrevPosition(Vec3::new(6.0, 0.0, 0.0));
+
+        assert_eq!(interpolate(&pos, Some(&prev), 0.0), prev.0);
+        assert_eq!(
+            interpolate(&pos, Some(&prev), 0.5),
+            Vec3::new(8.0, 0.0, 2.0)
+        );
+        assert_eq!(interpolate(&pos, Some(&prev), 1.0), pos.0);
+
+        // Entities that never move are always drawn where they are
+        assert_eq!(interpolate(&pos, None, 0.5), pos.0);
+    }
 }
@@ -0,0 +1,165 @@
+//! Golden image regression tests for the draw workload.
+//!
+//! Each scenario is rendered headlessly and compared against a reference image in
+//! `tests/golden`. When a comparison fails, the rendered frame and a diff image are written to
+//! `target/golden`. Run the tests with `UPDATE_GOLDEN=1` to accept the new output.
+
+use super::*;
+use crate::audio::Player;
+use crate::capture;
+use crate::clock::TIMESTEP;
+use crate::control::Binding;
+use crate::image::load_image;
+use std::fs;
+use std::path::{Path, PathBuf};
+use winit::event::{ElementState, KeyboardInput};
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/golden");
+
+// Fixed seed so fire animations start on the same frame every run
+const SEED: u64 = 0x5eed;
+const INC: u64 = 0x50b;
+
+// Blending and lighting use floats, which may round differently across platforms and libm
+const TOLERANCE: u8 = 2;
+
+/// Load the map, with the viewport on Jean.
+///
+/// Nothing advances the animation clocks, so every sprite shows its first frame.
+fn load_scenario() -> World {
+    let world = World::default();
+    load_world(
+        world.borrow::<AllStoragesViewMut>().unwrap(),
+        Random::new(SEED, INC),
+    );
+    register_systems(&world);
+
+    world
+}
+
+fn render(world: &World) -> Vec<u8> {
+    // Render the current state, not an interpolation from the previous simulation step
+    world.run(store_prev_positions).unwrap();
+    world.run_workload("draw").unwrap();
+
+    world.borrow::<UniqueView<Frame>>().unwrap().0.clone()
+}
+
+fn write_png(path: &Path, data: &[u8]) {
+    capture::write_png(path, data, WIDTH, HEIGHT).unwrap();
+}
+
+fn assert_golden(name: &str, frame: &[u8]) {
+    let golden_path = PathBuf::from(GOLDEN_DIR).join(format!("{}.png", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(GOLDEN_DIR).unwrap();
+        write_png(&golden_path, frame);
+        return;
+    }
+
+    let golden = fs::read(&golden_path).unwrap_or_else(|_| {
+        panic!(
+            "Missing {}; run with UPDATE_GOLDEN=1 to create it",
+            golden_path.display()
+        )
+    });
+    let (width, height, expected) = load_image(&golden);
+    assert_eq!((width, height), (WIDTH as isize, HEIGHT as isize));
+
+    // Highlight pixels beyond the tolerance in red over a dimmed copy of the expected frame
+    let mut diff = Vec::with_capacity(frame.len());
+    let mut mismatched = 0;
+    for (actual, expected) in frame.chunks_exact(4).zip(expected.chunks_exact(4)) {
+        let matches = actual
+            .iter()
+            .zip(expected)
+            .all(|(actual, expected)| actual.abs_diff(*expected) <= TOLERANCE);
+        if matches {
+            diff.extend_from_slice(&[expected[0] / 4, expected[1] / 4, expected[2] / 4, 0xff]);
+        } else {
+            diff.extend_from_slice(&[0xff, 0, 0, 0xff]);
+            mismatched += 1;
+        }
+    }
+
+    if mismatched > 0 {
+        let output_dir = PathBuf::from(OUTPUT_DIR);
+        fs::create_dir_all(&output_dir).unwrap();
+        write_png(&output_dir.join(format!("{}.png", name)), frame);
+        write_png(&output_dir.join(format!("{}-diff.png", name)), &diff);
+
+        panic!(
+            "{} pixels differ from {} by more than {}; see {}",
+            mismatched,
+            golden_path.display(),
+            TOLERANCE,
+            output_dir.display()
+        );
+    }
+}
+
+#[test]
+fn test_golden_spawn() {
+    let world = load_scenario();
+
+    assert_golden("spawn", &render(&world));
+}
+
+#[test]
+fn test_golden_frog_summoned() {
+    let world = load_scenario();
+    world.add_unique_non_sync(Audio(Player::null())).unwrap();
+
+    // Hold the summon key for one simulation step
+    world
+        .run(|mut controls: UniqueViewMut<Controls>| {
+            let scancode = match controls.0.bindings().summon_frog[0] {
+                Binding::Scancode(scancode) => scancode,
+                Binding::Key(_) => panic!("Needs a summon key by position"),
+            };
+
+            #[allow(deprecated)]
+            controls.0.update(KeyboardInput {
+                scancode,
+                state: ElementState::Pressed,
+                virtual_keycode: None,
+                modifiers: Default::default(),
+            });
+        })
+        .unwrap();
+    world
+        .borrow::<UniqueViewMut<GameClock>>()
+        .unwrap()
+        .advance(TIMESTEP);
+    world.run_workload("update").unwrap();
+
+    // Summoning a frog dismisses the intro message
+    assert_eq!(world.borrow::<View<Frog>>().unwrap().len(), 1);
+    assert!(world.borrow::<UniqueView<Hud>>().unwrap().message.is_none());
+
+    assert_golden("frog_summoned", &render(&world));
+}
+
+#[test]
+fn test_golden_outro_half_faded() {
+    let world = load_scenario();
+
+    world.add_unique(Outro(Duration::ZERO, 0.5)).unwrap();
+
+    assert_golden("outro_half_faded", &render(&world));
+}
+
+#[test]
+fn test_golden_fire_lights() {
+    let world = load_scenario();
+
+    world
+        .run(|mut viewport: UniqueViewMut<Viewport>| {
+            viewport.pos = Vec2::new(976.0, 8.0);
+        })
+        .unwrap();
+
+    assert_golden("fire_lights", &render(&world));
+}
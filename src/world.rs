@@ -1,12 +1,24 @@
-use crate::component::{Annihilate, Controls, Random, UpdateTime};
+use crate::clock::GameClock;
+use crate::component::{Annihilate, Controls, CosmeticRandom, Frame, Random, Stats};
 use crate::map::add_tilemap;
+use crate::system::{store_prev_positions, update_viewport};
 use shipyard::AllStoragesViewMut;
 
-pub(crate) fn load_world(storages: AllStoragesViewMut) {
-    storages.add_unique(Random::default());
-    storages.add_unique(UpdateTime::default());
+pub(crate) fn load_world(mut storages: AllStoragesViewMut, random: Random) {
+    storages.add_unique(random);
+    // Effects use a fixed seed of their own, so they never shift gameplay rolls for a given seed
+    storages.add_unique(CosmeticRandom(Random::from_seed(0).0));
+    storages.add_unique(GameClock::from_env());
     storages.add_unique(Controls::default());
     storages.add_unique(Annihilate(Vec::new()));
+    storages.add_unique(Frame::default());
+    storages.add_unique(Stats::default());
 
-    add_tilemap(storages, include_str!("../assets/tilemap.tmx"));
+    add_tilemap(&mut storages, include_str!("../assets/tilemap.tmx"));
+
+    // Start the camera on Jean, so the first frames don't sweep in from the map origin
+    storages.run(update_viewport).expect("Update viewport");
+    storages
+        .run(store_prev_positions)
+        .expect("Store previous positions");
 }